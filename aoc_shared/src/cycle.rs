@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The states of a deterministic process up to the point where it starts repeating itself.
+#[derive(Debug, Clone)]
+pub struct Cycle<T> {
+    states: Vec<T>,
+    start: usize,
+}
+
+impl<T> Cycle<T> {
+    /// The step where the first state of the cycle was seen.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The amount of steps it takes for a state in the cycle to appear again.
+    pub fn length(&self) -> usize {
+        self.states.len() - self.start
+    }
+
+    /// Gets the state after `step` steps, however big it is.
+    pub fn state_at(&self, step: usize) -> &T {
+        if step < self.start {
+            &self.states[step]
+        } else {
+            &self.states[self.start + ((step - self.start) % self.length())]
+        }
+    }
+}
+
+/// Repeatedly applies `next` from `initial` until a state that was already seen comes up again,
+/// comparing whole states, so different states can never be mistaken for each other.
+pub fn find_cycle<T: Clone + Eq + Hash>(initial: T, mut next: impl FnMut(&T) -> T) -> Cycle<T> {
+    let mut seen = HashMap::new();
+    let mut states = vec![];
+    let mut current = initial;
+    loop {
+        if let Some(&start) = seen.get(&current) {
+            return Cycle { states, start };
+        }
+        let following = next(&current);
+        seen.insert(current.clone(), states.len());
+        states.push(current);
+        current = following;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cycle::find_cycle;
+
+    #[test]
+    fn finds_cycle_with_tail() {
+        // 0 -> 1 -> 2 -> 3 -> 4 -> 2 -> ...
+        let cycle = find_cycle(0, |&x| if x == 4 { 2 } else { x + 1 });
+
+        assert_eq!(cycle.start(), 2);
+        assert_eq!(cycle.length(), 3);
+    }
+
+    #[test]
+    fn finds_cycle_from_the_start() {
+        let cycle = find_cycle(0, |&x| (x + 1) % 5);
+
+        assert_eq!(cycle.start(), 0);
+        assert_eq!(cycle.length(), 5);
+    }
+
+    #[test]
+    fn extrapolates_states() {
+        let cycle = find_cycle(0, |&x| if x == 4 { 2 } else { x + 1 });
+
+        let states: Vec<_> = (0..10).map(|i| *cycle.state_at(i)).collect();
+
+        assert_eq!(states, vec![0, 1, 2, 3, 4, 2, 3, 4, 2, 3]);
+        assert_eq!(*cycle.state_at(1_000_000_000), 4);
+    }
+
+    #[test]
+    fn does_not_confuse_states_with_equal_values() {
+        // The second value repeats before the actual cycle does
+        let values = [5, 7, 5, 9, 1];
+        let cycle = find_cycle(0, |&i| (i + 1) % values.len());
+
+        assert_eq!(cycle.length(), 5);
+        assert_eq!(values[*cycle.state_at(1_000_000_000)], 5);
+    }
+}
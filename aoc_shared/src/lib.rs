@@ -3,3 +3,5 @@ pub mod coords2d;
 pub mod vector2d;
 
 pub mod map2d;
+pub mod cycle;
+pub mod rng;
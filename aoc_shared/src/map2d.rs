@@ -57,6 +57,11 @@ impl<T> Map2D<T> {
             .flatten()
     }
 
+    /// Iterates over the tiles in reading order.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.tiles.iter()
+    }
+
     pub fn get_mut(&mut self, coords: Coords2D) -> Option<&mut T> {
         self.get_index(coords)
             .map(|i| self.tiles.get_mut(i))
@@ -126,7 +131,7 @@ impl<T: TryFrom<char>> Map2D<T> {
 }
 
 impl<T: Clone> Map2D<T> {
-    fn filled_with(tile: &T, width: usize, height: usize) -> Self {
+    pub fn filled_with(tile: &T, width: usize, height: usize) -> Self {
         let tiles = vec![tile.clone(); width * height];
         Self {
             tiles,
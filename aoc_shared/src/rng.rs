@@ -0,0 +1,81 @@
+/// A small seeded generator for reproducible test fixtures, so they don't need extra
+/// dependencies. It's a 64 bit linear congruential generator, which is plenty for random grids
+/// but nowhere near good enough for anything else.
+#[derive(Debug, Clone)]
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The next 31 random bits. The low bits of the state have short periods, so they're dropped.
+    pub fn next_u31(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) as u32
+    }
+
+    /// A number in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u31() as u64 % bound
+    }
+}
+
+/// Builds a grid of `width` by `height` tiles picked by `tile`, one line per row and without a
+/// trailing newline.
+pub fn random_grid(width: usize, height: usize, seed: u64, mut tile: impl FnMut(&mut Lcg) -> char) -> String {
+    let mut rng = Lcg::new(seed);
+    let mut lines = vec![];
+    for _ in 0..height {
+        let line: String = (0..width).map(|_| tile(&mut rng)).collect();
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// One [`random_grid`] for each size, seeded with its position in `sizes`, for tests that check
+/// an implementation against a simpler one over a few shapes of map.
+pub fn random_grids<'a>(
+    sizes: &'a [(usize, usize)],
+    mut tile: impl FnMut(&mut Lcg) -> char + 'a,
+) -> impl Iterator<Item = ((usize, usize), String)> + 'a {
+    sizes.iter().enumerate().map(move |(seed, &(width, height))| {
+        ((width, height), random_grid(width, height, seed as u64, &mut tile))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::{random_grid, random_grids, Lcg};
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let mut first = Lcg::new(42);
+        let mut second = Lcg::new(42);
+
+        for _ in 0..100 {
+            let value = first.below(10);
+            assert!(value < 10);
+            assert_eq!(value, second.below(10));
+        }
+        assert_ne!(Lcg::new(1).next_u31(), Lcg::new(2).next_u31());
+    }
+
+    #[test]
+    fn builds_grids_row_by_row() {
+        let grid = random_grid(3, 2, 7, |rng| if rng.below(2) == 0 { '.' } else { '#' });
+
+        assert_eq!(grid.lines().map(str::len).collect::<Vec<_>>(), [3, 3]);
+        assert_eq!(grid, random_grid(3, 2, 7, |rng| if rng.below(2) == 0 { '.' } else { '#' }));
+    }
+
+    #[test]
+    fn seeds_grids_by_position() {
+        let tile = |rng: &mut Lcg| char::from_digit(rng.below(10) as u32, 10).unwrap();
+
+        let grids = random_grids(&[(4, 1), (2, 3)], tile).collect::<Vec<_>>();
+
+        assert_eq!(grids[0], ((4, 1), random_grid(4, 1, 0, tile)));
+        assert_eq!(grids[1], ((2, 3), random_grid(2, 3, 1, tile)));
+    }
+}
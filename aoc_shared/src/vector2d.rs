@@ -66,3 +66,88 @@ impl Mul<usize> for Vector2D {
         Self(self.0 * rhs as isize, self.1 * rhs as isize)
     }
 }
+
+/// A straight line between two points, both included.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Segment2D(pub Vector2D, pub Vector2D);
+
+impl Segment2D {
+    /// Whether both segments share at least one point, touching ends included.
+    pub fn intersects(&self, other: &Segment2D) -> bool {
+        let d1 = orientation(other.0, other.1, self.0);
+        let d2 = orientation(other.0, other.1, self.1);
+        let d3 = orientation(self.0, self.1, other.0);
+        let d4 = orientation(self.0, self.1, other.1);
+
+        if d1 * d2 < 0 && d3 * d4 < 0 {
+            return true;
+        }
+        (d1 == 0 && other.bounds_contain(self.0))
+            || (d2 == 0 && other.bounds_contain(self.1))
+            || (d3 == 0 && self.bounds_contain(other.0))
+            || (d4 == 0 && self.bounds_contain(other.1))
+    }
+
+    /// Whether both segments lie on the same line and share more than a single point.
+    pub fn overlaps(&self, other: &Segment2D) -> bool {
+        let collinear = orientation(self.0, self.1, other.0) == 0 && orientation(self.0, self.1, other.1) == 0;
+        if !collinear {
+            return false;
+        }
+        // Project onto the axis the segment spans the most, so vertical segments work too
+        let project = |v: Vector2D| if (self.0.0 - self.1.0).abs() >= (self.0.1 - self.1.1).abs() { v.0 } else { v.1 };
+        let (a_min, a_max) = (min(project(self.0), project(self.1)), max(project(self.0), project(self.1)));
+        let (b_min, b_max) = (min(project(other.0), project(other.1)), max(project(other.0), project(other.1)));
+        min(a_max, b_max) > max(a_min, b_min)
+    }
+
+    fn bounds_contain(&self, point: Vector2D) -> bool {
+        let lower = self.0.min_2d(self.1);
+        let upper = self.0.max_2d(self.1);
+        (lower.0..=upper.0).contains(&point.0) && (lower.1..=upper.1).contains(&point.1)
+    }
+}
+
+/// Sign of the cross product of `a -> b` and `a -> c`: positive if `c` is counter-clockwise from
+/// the line, negative if clockwise, and zero if it's on it. Done in `i128` so it can't overflow.
+fn orientation(a: Vector2D, b: Vector2D, c: Vector2D) -> i8 {
+    let cross = (b.0 as i128 - a.0 as i128) * (c.1 as i128 - a.1 as i128)
+        - (b.1 as i128 - a.1 as i128) * (c.0 as i128 - a.0 as i128);
+    cross.signum() as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector2d::{Segment2D, Vector2D};
+
+    fn segment(x1: isize, y1: isize, x2: isize, y2: isize) -> Segment2D {
+        Segment2D(Vector2D(x1, y1), Vector2D(x2, y2))
+    }
+
+    #[test]
+    fn crossing_segments_intersect() {
+        assert!(segment(0, 0, 4, 0).intersects(&segment(2, -2, 2, 2)));
+        assert!(segment(0, 0, 4, 4).intersects(&segment(0, 4, 4, 0)));
+    }
+
+    #[test]
+    fn touching_segments_intersect() {
+        assert!(segment(0, 0, 4, 0).intersects(&segment(4, 0, 4, 4)));
+        assert!(segment(0, 0, 4, 0).intersects(&segment(2, 0, 2, 3)));
+    }
+
+    #[test]
+    fn separate_segments_do_not_intersect() {
+        assert!(!segment(0, 0, 4, 0).intersects(&segment(5, 0, 8, 0)));
+        assert!(!segment(0, 0, 4, 0).intersects(&segment(2, 1, 2, 3)));
+        assert!(!segment(0, 0, 4, 4).intersects(&segment(1, 0, 5, 4)));
+    }
+
+    #[test]
+    fn detects_overlaps() {
+        assert!(segment(0, 0, 4, 0).overlaps(&segment(6, 0, 2, 0)));
+        assert!(segment(0, 0, 0, 4).overlaps(&segment(0, 1, 0, 2)));
+        assert!(!segment(0, 0, 4, 0).overlaps(&segment(4, 0, 8, 0)));
+        assert!(!segment(0, 0, 4, 0).overlaps(&segment(0, 1, 4, 1)));
+    }
+}
@@ -1,21 +1,37 @@
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 use std::io::{BufRead, stdin};
-use std::ops::Range;
 
 fn main() {
     let universe = Universe::parse(stdin().lock());
     println!("Empty rows ({}): {:?}", universe.empty_rows.len(), universe.empty_rows);
-    println!("Empty columns ({}): {:?}", universe.empty_rows.len(), universe.empty_columns);
-    
-    let pairs = universe.get_galaxy_pairs();
-    // println!("Pairs ({}): {:?}", pairs.len(), &pairs);
-    
-    let expansion = 1000000;
-    let sum: usize = pairs
-        .iter()
-        .map(|p| universe.get_distance(p, expansion))
-        .sum();
-    println!("Sum: {}", sum);
+    println!("Empty columns ({}): {:?}", universe.empty_columns.len(), universe.empty_columns);
+
+    match universe.sum_of_distances(&[2, 1000000]) {
+        Ok(sums) => {
+            println!("Part 1: {}", sums[0]);
+            println!("Part 2: {}", sums[1]);
+        }
+        Err(err) => println!("{}", err),
+    }
+
+    let expanded = match universe.expand(1000000, 1000000) {
+        Ok(expanded) => expanded,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    if let Some((a, b, distance)) = expanded.farthest_pair() {
+        println!("Farthest pair: {:?} and {:?} ({} apart)", a, b, distance);
+    }
+    let closest_distance = (0..expanded.galaxies().len())
+        .filter_map(|i| expanded.nearest_neighbour(i))
+        .map(|(_, distance)| distance)
+        .min();
+    if let Some(distance) = closest_distance {
+        println!("Closest pair: {} apart", distance);
+    }
 }
 
 enum Tile {
@@ -30,6 +46,29 @@ impl Point2D {
     fn new(x: usize, y: usize) -> Self {
         Self(x, y)
     }
+
+    fn manhattan_distance_to(&self, other: Point2D) -> usize {
+        self.0.abs_diff(other.0) + self.1.abs_diff(other.1)
+    }
+}
+
+/// An expansion factor of 0, which would make empty lines take less than no space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ZeroExpansion {
+    /// The factor at `index` of a list of them.
+    Factor { index: usize },
+    Columns,
+    Rows,
+}
+
+impl Display for ZeroExpansion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Factor { index } => write!(f, "Expansion factor {} is 0, it has to be at least 1", index),
+            Self::Columns => write!(f, "Columns can't expand by a factor of 0"),
+            Self::Rows => write!(f, "Rows can't expand by a factor of 0"),
+        }
+    }
 }
 
 struct Universe {
@@ -96,65 +135,227 @@ impl Universe {
         }
     }
     
-    fn get_galaxy_pairs(&self) -> Vec<GalaxyPair> {
-        let mut pairs = vec![];
-        let galaxies: Vec<_> = self.galaxies.iter().cloned().collect();
-        for (i, &galaxy) in galaxies.iter().enumerate() {
-            let pair_with = &galaxies[(i + 1)..];
-            for &other in pair_with.iter() {
-                pairs.push(GalaxyPair(galaxy, other));
-            }
+    /// Sums the distances between every pair of galaxies, once per expansion factor.
+    ///
+    /// Each axis is handled independently: with the coordinates sorted, a galaxy's expanded
+    /// position is `x + empty_before(x) * (expansion - 1)`, so the sum of pairwise distances is
+    /// `raw_sum + empty_sum * (expansion - 1)` where both sums come from the same prefix sum pass.
+    fn sum_of_distances(&self, expansions: &[usize]) -> Result<Vec<usize>, ZeroExpansion> {
+        if let Some(index) = expansions.iter().position(|&expansion| expansion == 0) {
+            return Err(ZeroExpansion::Factor { index });
         }
-        pairs
+        let (raw_x, empty_x) = Self::axis_distance_sums(
+            self.galaxies.iter().map(|p| p.0).collect(),
+            &self.empty_columns,
+        );
+        let (raw_y, empty_y) = Self::axis_distance_sums(
+            self.galaxies.iter().map(|p| p.1).collect(),
+            &self.empty_rows,
+        );
+        let raw = raw_x + raw_y;
+        let empty = empty_x + empty_y;
+
+        Ok(expansions
+            .iter()
+            .map(|&expansion| raw + empty * (expansion - 1))
+            .collect())
     }
-    
-    fn get_distance(&self, pair: &GalaxyPair, expansion: usize) -> usize {
-        let a = pair.0;
-        let b = pair.1;
-        let x_distance = a.0.abs_diff(b.0);
-        let y_distance = a.1.abs_diff(b.1);
-        let raw_distance = x_distance + y_distance;
 
-        // println!();
-        let empty_columns = self.get_empty_columns(a.0..b.0).len();
-        let empty_rows = self.get_empty_rows(a.1..b.1).len();
+    /// Returns the universe with every empty column grown to `column_expansion` columns and every
+    /// empty row grown to `row_expansion` rows.
+    fn expand(&self, column_expansion: usize, row_expansion: usize) -> Result<ExpandedUniverse, ZeroExpansion> {
+        if column_expansion == 0 {
+            return Err(ZeroExpansion::Columns);
+        }
+        if row_expansion == 0 {
+            return Err(ZeroExpansion::Rows);
+        }
+        let expand_axis = |coord: usize, empty_lines: &[usize], expansion: usize| {
+            coord + empty_lines.partition_point(|&line| line < coord) * (expansion - 1)
+        };
 
-        let distance = raw_distance + ((empty_rows + empty_columns) * (expansion - 1));
-        // println!("Distance from {:?} to {:?} = {} {:?}", &a, &b, raw_distance, &(x_distance, y_distance));
-        // println!("+ ({} empty rows + {} empty_columns)", empty_rows, empty_columns);
-        // println!("= * {}", expansion - 1);
-        // println!("= {}", distance);
+        let mut galaxies: Vec<_> = self.galaxies
+            .iter()
+            .map(|p| Point2D::new(
+                expand_axis(p.0, &self.empty_columns, column_expansion),
+                expand_axis(p.1, &self.empty_rows, row_expansion),
+            ))
+            .collect();
+        // Reading order, so indices are stable between runs
+        galaxies.sort_unstable_by_key(|p| (p.1, p.0));
 
-        distance
+        Ok(ExpandedUniverse {
+            width: self.width + self.empty_columns.len() * (column_expansion - 1),
+            height: self.height + self.empty_rows.len() * (row_expansion - 1),
+            galaxies,
+        })
     }
-    
-    fn get_empty_columns(&self, range: Range<usize>) -> &[usize] {
-        Self::get_range(&self.empty_columns, range)
+
+    /// Returns the sum of pairwise distances between the given coordinates, and the sum of
+    /// pairwise counts of empty lines between them.
+    fn axis_distance_sums(mut coords: Vec<usize>, empty_lines: &[usize]) -> (usize, usize) {
+        coords.sort_unstable();
+
+        let mut raw_sum = 0;
+        let mut empty_sum = 0;
+        let mut raw_prefix = 0;
+        let mut empty_prefix = 0;
+        for (i, &coord) in coords.iter().enumerate() {
+            let empty_before = empty_lines.partition_point(|&line| line < coord);
+            // Every previous coordinate is smaller or equal, so the distance to each of them is
+            // just the difference, which adds up to `coord * i - prefix`.
+            raw_sum += coord * i - raw_prefix;
+            empty_sum += empty_before * i - empty_prefix;
+            raw_prefix += coord;
+            empty_prefix += empty_before;
+        }
+        (raw_sum, empty_sum)
     }
-    
-    fn get_empty_rows(&self, range: Range<usize>) -> &[usize] {
-        Self::get_range(&self.empty_rows, range)
+}
+
+struct ExpandedUniverse {
+    width: usize,
+    height: usize,
+    galaxies: Vec<Point2D>,
+}
+
+impl ExpandedUniverse {
+    /// The expanded coordinates of every galaxy, in reading order.
+    fn galaxies(&self) -> &[Point2D] {
+        &self.galaxies
     }
-    
-    fn get_range(nums: &[usize], range: Range<usize>) -> &[usize] {
-        let actual_range = if range.start <= range.end {
-            range
-        } else {
-            range.end..range.start
-        };
-        let from = match nums.binary_search(&actual_range.start) {
-            Ok(i) => i,
-            Err(i) => i,
-        };
-        let to = match nums.binary_search(&actual_range.end) {
-            Ok(i) => i,
-            Err(i) => i,
-        };
-        let result = &nums[from..to];
-        // println!("{:?} {:?} -> {:?} ({:?})", actual_range, nums, &result, from..to);
-        result
+
+    /// Returns the index of the galaxy closest to the galaxy at `index`, and the distance to it.
+    fn nearest_neighbour(&self, index: usize) -> Option<(usize, usize)> {
+        let galaxy = *self.galaxies.get(index)?;
+        self.galaxies
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != index)
+            .map(|(i, other)| (i, galaxy.manhattan_distance_to(*other)))
+            .min_by_key(|&(_, distance)| distance)
+    }
+
+    /// Returns the two galaxies that are the farthest apart, and the distance between them.
+    ///
+    /// The manhattan distance between two points is the biggest of the differences of their
+    /// `x + y` and `x - y`, so only the extremes of those two values need to be compared.
+    fn farthest_pair(&self) -> Option<(Point2D, Point2D, usize)> {
+        let sum = |p: &&Point2D| p.0 as isize + p.1 as isize;
+        let diff = |p: &&Point2D| p.0 as isize - p.1 as isize;
+
+        let by_sum = (self.galaxies.iter().min_by_key(sum)?, self.galaxies.iter().max_by_key(sum)?);
+        let by_diff = (self.galaxies.iter().min_by_key(diff)?, self.galaxies.iter().max_by_key(diff)?);
+
+        [by_sum, by_diff]
+            .into_iter()
+            .map(|(&a, &b)| (a, b, a.manhattan_distance_to(b)))
+            .max_by_key(|&(_, _, distance)| distance)
+    }
+}
+
+/// Renders the universe back to the puzzle's format, only meant for small expansion factors.
+impl Display for ExpandedUniverse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut galaxies = self.galaxies.iter().peekable();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if galaxies.next_if(|&&p| p == Point2D::new(x, y)).is_some() {
+                    write!(f, "#")?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-struct GalaxyPair(Point2D, Point2D);
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{ZeroExpansion, Point2D, Universe};
+
+    const EXAMPLE: &str = "\
+...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
+    #[test]
+    fn sums_distances_for_all_expansions() {
+        let universe = Universe::parse(Cursor::new(EXAMPLE));
+
+        let result = universe.sum_of_distances(&[1, 2, 10, 100]);
+
+        assert_eq!(result, Ok(vec![292, 374, 1030, 8410]));
+    }
+
+    #[test]
+    fn rejects_zero_expansion() {
+        let universe = Universe::parse(Cursor::new(EXAMPLE));
+
+        let result = universe.sum_of_distances(&[2, 0]);
+
+        assert_eq!(result, Err(ZeroExpansion::Factor { index: 1 }));
+    }
+
+    #[test]
+    fn expands_rows_and_columns_separately() {
+        let universe = Universe::parse(Cursor::new("#..\n...\n..#"));
+
+        let expanded = universe.expand(3, 2).unwrap();
+
+        assert_eq!(expanded.galaxies(), &[Point2D(0, 0), Point2D(4, 3)]);
+        assert_eq!((expanded.width, expanded.height), (5, 4));
+    }
+
+    #[test]
+    fn rejects_zero_expansion_on_either_axis() {
+        let universe = Universe::parse(Cursor::new("#..\n...\n..#"));
+
+        assert_eq!(universe.expand(0, 2).err(), Some(ZeroExpansion::Columns));
+        assert_eq!(universe.expand(2, 0).err(), Some(ZeroExpansion::Rows));
+    }
+
+    #[test]
+    fn renders_expanded_universe() {
+        let universe = Universe::parse(Cursor::new(EXAMPLE));
+
+        let expanded = universe.expand(2, 2).unwrap();
+
+        let expected = "\
+....#........
+.........#...
+#............
+.............
+.............
+........#....
+.#...........
+............#
+.............
+.............
+.........#...
+#....#.......
+";
+        assert_eq!(expanded.to_string(), expected);
+    }
+
+    #[test]
+    fn finds_nearest_and_farthest_galaxies() {
+        let universe = Universe::parse(Cursor::new(EXAMPLE));
+
+        let expanded = universe.expand(2, 2).unwrap();
+
+        // Galaxy 8 is closest to galaxy 9, and galaxies 2 and 8 are the farthest apart
+        assert_eq!(expanded.nearest_neighbour(7), Some((8, 5)));
+        assert_eq!(expanded.farthest_pair(), Some((Point2D(0, 11), Point2D(9, 1), 19)));
+    }
+}
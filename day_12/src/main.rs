@@ -1,149 +1,129 @@
 use std::cmp::min;
-use std::collections::HashMap;
-use std::fmt::Write;
+use std::fmt::{Display, Formatter, Write};
 use std::io::{BufRead, stdin};
-use std::ops::Range;
+use std::num::ParseIntError;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 
 fn main() {
-    use std::time::Instant;
     let now = Instant::now();
-    
-    let mut sum = 0;
-    for (i, line_result) in stdin().lock().lines().enumerate() {
-        let local_now = Instant::now();
-        let line = line_result.unwrap();
-        let row = Row::from_str(&line).unwrap();
-        // println!("{:?}", &row);
-        let result = row.find_possible_combinations();
-        sum += result;
-        println!("{}) [{} element/s] -> {}", i + 1, row.tiles.len(), result);
-        println!(" - Elapsed: {:?}", local_now.elapsed());
+    let options = Options::from_args(std::env::args().skip(1));
+
+    let lines: Vec<_> = stdin().lock().lines().map(|l| l.unwrap()).collect();
+    let parse_rows = |fold| {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| Row::parse(line, fold).unwrap_or_else(|err| panic!("Line {}: {}", i + 1, err)))
+            .collect::<Vec<_>>()
+    };
+    let folded_rows = parse_rows(1);
+    let unfolded_rows = parse_rows(5);
+
+    if options.show_arrangements {
+        for (i, row) in folded_rows.iter().enumerate() {
+            println!("{}) {}", i + 1, &lines[i]);
+            for arrangement in row.arrangements() {
+                let text: String = arrangement.iter().map(|tile| format!("{:?}", tile)).collect();
+                println!("  {}", text);
+            }
+        }
     }
-    println!("Total elapsed: {:?}", now.elapsed());
-    println!("{sum}");
-
-    /*
-    let rows: Vec<_> = stdin()
-        .lock()
-        .lines()
-        .enumerate()
-        .map(|(i, s)| {
-            (i + 1, Row::from_str(&s.unwrap()).unwrap())
-        })
-        .collect();
-
-    let progress = Arc::new(Mutex::new(0));
-    let total_length = rows.len();
-    let sum: usize = rows
-        .into_par_iter()
-        .map(move |(i, row)| {
-            let progress_ref = progress.clone();
-            let local_now = Instant::now();
-            let result = row.find_possible_combinations();
 
-            let current_progress = {
-                let mut mutex = progress_ref.lock().unwrap();
-                let current_progress = *mutex + 1;
-                *mutex = current_progress;
-                current_progress
-            };
-            let current_time = now.elapsed();
-            println!("({}/{}) N°{} finished. Current elapsed: {:?}, Total elapsed: {:?}",
-                current_progress, total_length, i + 1, local_now.elapsed(), current_time);
-            if current_progress % 5 == 0 {
-                let average_time = current_time / current_progress;
-                let estimated_time = average_time * total_length as u32;
-                let eta = estimated_time - current_time;
-                println!("ETA: {eta:?}, Average time: {average_time:?}, Total estimated: {estimated_time:?}");
-            }
-            result
-        })
-        .sum();
+    let print_progress = |progress: &Progress| {
+        // Reporting every row would flood the output on real inputs
+        if progress.completed.is_multiple_of(50) || progress.completed == progress.total {
+            println!("({}/{}) Elapsed: {:?}, ETA: {:?}", progress.completed, progress.total, progress.elapsed, progress.eta());
+        }
+    };
+    let part_1: usize = solve_batch(&folded_rows, options.jobs, &print_progress).iter().sum();
+    let part_2: usize = solve_batch(&unfolded_rows, options.jobs, &print_progress).iter().sum();
 
     println!("Total elapsed: {:?}", now.elapsed());
-    println!("{sum}");
-    */
+    println!("Part 1: {part_1}");
+    println!("Part 2: {part_2}");
 }
 
-// Answer copied from https://pastebin.com/1EAdWWMk
-// I tried to learn from it at the very least
-fn find_possible_combinations_cached(
-    cache: &mut HashMap<(usize, usize, u16), usize>,
-    tiles: &[Tile],
-    groups: &[u16],
-    from_i: usize,
-    group_i: usize,
-    size: u16,
-) -> usize {
-    if from_i >= tiles.len() {
-        // Exhausted all groups
-        if group_i >= groups.len() {
-            return 1;
-        }
-        
-        // The line ends with a "damaged" symbol and we've matched that last group
-        if group_i >= groups.len() - 1 && groups[group_i] == size {
-            return 1;
-        }
-        
-        return 0;
-    }
-
-    return match tiles[from_i] {
-        Tile::Operational => {
-            // Skip sequence of operational spots
-            if size == 0 {
-                return find_possible_combinations_cached(cache, tiles, groups, from_i + 1, group_i, size);
-            }
-
-            // The current combination failed to match a proper sequence from the input
-            if group_i >= groups.len() || size != groups[group_i] {
-                return 0;
-            }
+struct Options {
+    jobs: Option<usize>,
+    show_arrangements: bool,
+}
 
-            // we have a match: process the next group
-            find_possible_combinations_cached(cache, tiles, groups, from_i + 1, group_i + 1, 0)
-        },
-        Tile::Damaged => {
-            // We don't expect any other damaged spots, failed to match
-            if group_i >= groups.len() || size + 1 > groups[group_i] {
-                return 0;
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = Options { jobs: None, show_arrangements: false };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--jobs" | "-j" => {
+                    let jobs = args.next().and_then(|n| n.parse().ok()).expect("--jobs expects a number");
+                    options.jobs = Some(jobs);
+                },
+                "--arrangements" => options.show_arrangements = true,
+                _ => panic!("Unexpected argument: {}", arg),
             }
+        }
+        options
+    }
+}
 
-            find_possible_combinations_cached(cache, tiles, groups, from_i + 1, group_i, size + 1)
-        },
-        Tile::Unknown => {
-            if let Some(&answer) = cache.get(&(from_i, group_i, size)) {
-                return answer;
-            }
-            
-            let mut combinations = 0;
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    completed: usize,
+    total: usize,
+    elapsed: Duration,
+}
 
-            // if we did not encounter any damaged cells,
-            // we can treat this one as undamaged
-            if size == 0 {
-                combinations += find_possible_combinations_cached(cache, tiles, groups, from_i + 1, group_i, size);
-            }
+impl Progress {
+    /// Estimated time left, assuming the remaining rows take as long as the average so far.
+    fn eta(&self) -> Option<Duration> {
+        if self.completed == 0 {
+            return None;
+        }
+        let average = self.elapsed / self.completed as u32;
+        Some(average * (self.total - self.completed) as u32)
+    }
+}
 
-            // If we need more damaged cells to complete our match,
-            // we can treat the current cell as damaged
-            if group_i < groups.len() && size < groups[group_i] {
-                combinations += find_possible_combinations_cached(cache, tiles, groups, from_i + 1, group_i, size + 1);
-            }
+/// Receives a report every time a row of a batch is solved, possibly from several threads at once.
+trait ProgressSink: Sync {
+    fn report(&self, progress: &Progress);
+}
 
-            // We have the correct number of damaged cells, so we can just
-            // treat this one as undamaged in order to complete the match
-            if group_i < groups.len() && size == groups[group_i] {
-                combinations += find_possible_combinations_cached(cache, tiles, groups, from_i + 1, group_i + 1, 0);
-            }
+impl<F: Fn(&Progress) + Sync> ProgressSink for F {
+    fn report(&self, progress: &Progress) {
+        self(progress)
+    }
+}
 
-            cache.insert((from_i, group_i, size), combinations);
-            combinations
-        },
+/// Solves every row in parallel, using `jobs` threads or all of them if `None`, and returns
+/// the combinations of each row in input order.
+fn solve_batch(rows: &[Row], jobs: Option<usize>, sink: &impl ProgressSink) -> Vec<usize> {
+    let now = Instant::now();
+    let completed = AtomicUsize::new(0);
+    let solve = || {
+        rows
+            .par_iter()
+            .map(|row| {
+                let result = row.find_possible_combinations();
+                let progress = Progress {
+                    completed: completed.fetch_add(1, Ordering::Relaxed) + 1,
+                    total: rows.len(),
+                    elapsed: now.elapsed(),
+                };
+                sink.report(&progress);
+                result
+            })
+            .collect()
+    };
+
+    let mut pool = ThreadPoolBuilder::new();
+    if let Some(jobs) = jobs {
+        pool = pool.num_threads(jobs);
     }
+    pool.build().unwrap().install(solve)
 }
 
 #[derive(Debug)]
@@ -152,49 +132,186 @@ struct Row {
     known_sequence: Vec<u16>,
 }
 
-impl Row {
-    fn find_possible_combinations(&self) -> usize {
-        let mut cache = HashMap::default();
-        find_possible_combinations_cached(&mut cache, &self.tiles, &self.known_sequence, 0, 0, 0)
-    }
+#[derive(Debug)]
+enum ParseRowError {
+    MissingTiles,
+    MissingSequence,
+    InvalidTile(InvalidCharError),
+    InvalidGroup(ParseIntError),
 }
 
-impl FromStr for Row {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        const EXTEND: usize = 5;
+impl Display for ParseRowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingTiles => write!(f, "no tiles found in row"),
+            Self::MissingSequence => write!(f, "no sequence found in row"),
+            Self::InvalidTile(err) => write!(f, "invalid tile: {:?}", err.c),
+            Self::InvalidGroup(err) => write!(f, "invalid group size: {}", err),
+        }
+    }
+}
 
+impl Row {
+    /// Parses a row, unfolding it `fold` times: the tiles are repeated with an unknown tile
+    /// between each copy, and the sequence is repeated as is.
+    fn parse(s: &str, fold: usize) -> Result<Self, ParseRowError> {
         let mut split = s.split(' ');
-        
-        let tiles_str = split.next().expect("No tiles found in str.");
-        let temp_tiles: Vec<_> = tiles_str
+
+        let tiles_str = split.next().filter(|s| !s.is_empty()).ok_or(ParseRowError::MissingTiles)?;
+        let folded_tiles: Vec<_> = tiles_str
             .chars()
             .map(Tile::try_from)
             .collect::<Result<_, _>>()
-            .unwrap();
-        let mut tiles = temp_tiles.clone();
-        for _ in 0..(EXTEND - 1) {
+            .map_err(ParseRowError::InvalidTile)?;
+        let mut tiles = folded_tiles.clone();
+        for _ in 1..fold {
             tiles.push(Tile::Unknown);
-            tiles.extend(temp_tiles.iter());
+            tiles.extend(folded_tiles.iter());
         }
 
-        let sequence_str = split.next().expect("No sequence found in str.");
-        let temp_known_sequence: Vec<_> = sequence_str
+        let sequence_str = split.next().ok_or(ParseRowError::MissingSequence)?;
+        let folded_sequence: Vec<_> = sequence_str
             .split(',')
             .map(u16::from_str)
             .collect::<Result<_, _>>()
-            .unwrap();
-        let mut known_sequence = temp_known_sequence.clone();
-        for _ in 0..(EXTEND - 1) {
-            known_sequence.extend(temp_known_sequence.iter());
-        }
-        
+            .map_err(ParseRowError::InvalidGroup)?;
+        let known_sequence = folded_sequence.repeat(fold);
+
         Ok(Row { tiles, known_sequence })
     }
+
+    fn find_possible_combinations(&self) -> usize {
+        self.combinations_table().get(0, 0)
+    }
+
+    /// Iterates over every concrete arrangement of the row, only meant for small rows since
+    /// there can be as many as `2^unknowns` of them.
+    fn arrangements(&self) -> Arrangements<'_> {
+        Arrangements {
+            row: self,
+            table: self.combinations_table(),
+            operational_prefix: self.operational_prefix(),
+            stack: vec![(0, 0, Vec::with_capacity(self.tiles.len()))],
+        }
+    }
+
+    /// Amount of operational tiles before each index, to check whether a group fits in O(1).
+    fn operational_prefix(&self) -> Vec<usize> {
+        let mut prefix = Vec::with_capacity(self.tiles.len() + 1);
+        prefix.push(0);
+        for tile in &self.tiles {
+            let previous = *prefix.last().unwrap();
+            prefix.push(previous + usize::from(*tile == Tile::Operational));
+        }
+        prefix
+    }
+
+    /// Returns whether `group_i` can start at `tile_i`: the group must only cover tiles that can be
+    /// damaged, and it can't be immediately followed by another damaged tile.
+    fn group_fits(&self, operational_prefix: &[usize], tile_i: usize, group_i: usize) -> bool {
+        let end = tile_i + self.known_sequence[group_i] as usize;
+        end <= self.tiles.len()
+            && operational_prefix[end] == operational_prefix[tile_i]
+            && !self.tiles.get(end).is_some_and(Tile::is_damaged)
+    }
+
+    /// Builds the table of combinations bottom-up, where each cell holds the number of ways
+    /// `tiles[tile_i..]` can be arranged into `known_sequence[group_i..]`, assuming the tile
+    /// before `tile_i` isn't damaged.
+    fn combinations_table(&self) -> CombinationsTable {
+        let tiles_len = self.tiles.len();
+        let groups_len = self.known_sequence.len();
+        let operational_prefix = self.operational_prefix();
+
+        let mut table = CombinationsTable {
+            cells: vec![0; (tiles_len + 1) * (groups_len + 1)],
+            groups_len,
+        };
+        table.set(tiles_len, groups_len, 1);
+        for tile_i in (0..tiles_len).rev() {
+            for group_i in 0..=groups_len {
+                let mut combinations = 0;
+                if self.tiles[tile_i] != Tile::Damaged {
+                    combinations += table.get(tile_i + 1, group_i);
+                }
+                if group_i < groups_len && self.group_fits(&operational_prefix, tile_i, group_i) {
+                    // Skip the group and the operational tile right after it
+                    let next_i = min(tile_i + self.known_sequence[group_i] as usize + 1, tiles_len);
+                    combinations += table.get(next_i, group_i + 1);
+                }
+                table.set(tile_i, group_i, combinations);
+            }
+        }
+        table
+    }
+}
+
+struct CombinationsTable {
+    cells: Vec<usize>,
+    groups_len: usize,
+}
+
+impl CombinationsTable {
+    fn get(&self, tile_i: usize, group_i: usize) -> usize {
+        self.cells[tile_i * (self.groups_len + 1) + group_i]
+    }
+
+    fn set(&mut self, tile_i: usize, group_i: usize, value: usize) {
+        self.cells[tile_i * (self.groups_len + 1) + group_i] = value;
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// Depth first walk over the combinations table that only visits cells with at least one
+/// combination, so every branch it takes ends in an arrangement.
+struct Arrangements<'a> {
+    row: &'a Row,
+    table: CombinationsTable,
+    operational_prefix: Vec<usize>,
+    stack: Vec<(usize, usize, Vec<Tile>)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<Tile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tiles = &self.row.tiles;
+        let groups = &self.row.known_sequence;
+        while let Some((tile_i, group_i, arrangement)) = self.stack.pop() {
+            if self.table.get(tile_i, group_i) == 0 {
+                continue;
+            }
+            if tile_i == tiles.len() {
+                return Some(arrangement);
+            }
+
+            if tiles[tile_i] != Tile::Damaged {
+                let mut operational = arrangement.clone();
+                operational.push(Tile::Operational);
+                self.stack.push((tile_i + 1, group_i, operational));
+            }
+            if group_i < groups.len() && self.row.group_fits(&self.operational_prefix, tile_i, group_i) {
+                let end = tile_i + groups[group_i] as usize;
+                let mut damaged = arrangement;
+                damaged.extend((tile_i..end).map(|_| Tile::Damaged));
+                if end < tiles.len() {
+                    damaged.push(Tile::Operational);
+                }
+                self.stack.push((min(end + 1, tiles.len()), group_i + 1, damaged));
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for Row {
+    type Err = ParseRowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, 1)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Tile {
     Unknown,
     Operational,
@@ -252,44 +369,169 @@ mod tests {
         assert_eq!(&row.known_sequence[..], &expected_sequence);
     }
 
+    #[test]
+    fn row_unfolds_correctly() {
+        let row = Row::parse(".# 1", 5).unwrap();
+        let expected_tiles = [
+            Operational, Damaged, Unknown,
+            Operational, Damaged, Unknown,
+            Operational, Damaged, Unknown,
+            Operational, Damaged, Unknown,
+            Operational, Damaged,
+        ];
+
+        assert_eq!(&row.tiles[..], &expected_tiles);
+        assert_eq!(&row.known_sequence[..], &[1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn row_fails_to_parse_invalid_input() {
+        assert!(Row::parse("", 1).is_err());
+        assert!(Row::parse("???.###", 1).is_err());
+        assert!(Row::parse("??x.### 1,1,3", 1).is_err());
+        assert!(Row::parse("???.### 1,a,3", 1).is_err());
+    }
+
     mod examples {
-        use std::str::FromStr;
         use crate::Row;
 
-        fn assert_combinations(s: &str, expected: usize) {
-            let row = Row::from_str(s).unwrap();
+        fn assert_combinations(s: &str, fold: usize, expected: usize) {
+            let row = Row::parse(s, fold).unwrap();
             let result = row.find_possible_combinations();
             assert_eq!(result, expected);
         }
 
         #[test]
         fn returns_correct_combinations_case_1() {
-            assert_combinations("???.### 1,1,3", 1);
+            assert_combinations("???.### 1,1,3", 1, 1);
+            assert_combinations("???.### 1,1,3", 5, 1);
         }
 
         #[test]
         fn returns_correct_combinations_case_2() {
-            assert_combinations(".??..??...?##. 1,1,3", 4);
+            assert_combinations(".??..??...?##. 1,1,3", 1, 4);
+            assert_combinations(".??..??...?##. 1,1,3", 5, 16384);
         }
 
         #[test]
         fn returns_correct_combinations_case_3() {
-            assert_combinations("?#?#?#?#?#?#?#? 1,3,1,6", 1);
+            assert_combinations("?#?#?#?#?#?#?#? 1,3,1,6", 1, 1);
+            assert_combinations("?#?#?#?#?#?#?#? 1,3,1,6", 5, 1);
         }
 
         #[test]
         fn returns_correct_combinations_case_4() {
-            assert_combinations("????.#...#... 4,1,1", 1);
+            assert_combinations("????.#...#... 4,1,1", 1, 1);
+            assert_combinations("????.#...#... 4,1,1", 5, 16);
         }
 
         #[test]
         fn returns_correct_combinations_case_5() {
-            assert_combinations("????.######..#####. 1,6,5", 4);
+            assert_combinations("????.######..#####. 1,6,5", 1, 4);
+            assert_combinations("????.######..#####. 1,6,5", 5, 2500);
         }
 
         #[test]
         fn returns_correct_combinations_case_6() {
-            assert_combinations("?###???????? 3,2,1", 10);
+            assert_combinations("?###???????? 3,2,1", 1, 10);
+            assert_combinations("?###???????? 3,2,1", 5, 506250);
+        }
+    }
+
+    mod batch {
+        use std::sync::Mutex;
+        use crate::{Progress, Row, solve_batch};
+
+        #[test]
+        fn returns_results_in_input_order_and_reports_every_row() {
+            let rows: Vec<_> = [
+                "???.### 1,1,3",
+                ".??..??...?##. 1,1,3",
+                "?#?#?#?#?#?#?#? 1,3,1,6",
+                "????.#...#... 4,1,1",
+                "????.######..#####. 1,6,5",
+                "?###???????? 3,2,1",
+            ]
+                .iter()
+                .map(|s| Row::parse(s, 5).unwrap())
+                .collect();
+            let reports = Mutex::new(vec![]);
+
+            let result = solve_batch(&rows, Some(3), &|progress: &Progress| {
+                reports.lock().unwrap().push(progress.completed);
+            });
+
+            let mut reports = reports.into_inner().unwrap();
+            reports.sort_unstable();
+            assert_eq!(result, vec![1, 16384, 1, 16, 2500, 506250]);
+            assert_eq!(reports, vec![1, 2, 3, 4, 5, 6]);
+        }
+    }
+
+    mod brute_force {
+        use std::collections::HashSet;
+        use aoc_shared::rng::Lcg;
+        use crate::{Row, Tile};
+        use crate::Tile::*;
+
+        fn groups_of(tiles: &[Tile]) -> Vec<u16> {
+            tiles
+                .split(|t| *t == Operational)
+                .filter(|group| !group.is_empty())
+                .map(|group| group.len() as u16)
+                .collect()
+        }
+
+        fn brute_force_arrangements(row: &Row) -> HashSet<Vec<Tile>> {
+            let unknowns: Vec<_> = (0..row.tiles.len()).filter(|&i| row.tiles[i] == Unknown).collect();
+            (0..(1u32 << unknowns.len()))
+                .map(|mask| {
+                    let mut tiles = row.tiles.clone();
+                    for (bit, &i) in unknowns.iter().enumerate() {
+                        tiles[i] = if mask & (1 << bit) != 0 { Damaged } else { Operational };
+                    }
+                    tiles
+                })
+                .filter(|tiles| groups_of(tiles) == row.known_sequence)
+                .collect()
+        }
+
+        /// Random rows of up to 15 tiles, always the same ones so failures can be reproduced.
+        fn random_rows(count: usize) -> Vec<Row> {
+            let mut rng = Lcg::new(0x2023_1212);
+            let mut next = move |bound: u64| rng.below(bound);
+            (0..count)
+                .map(|_| {
+                    let tiles = (0..(1 + next(14)))
+                        .map(|_| [Unknown, Unknown, Operational, Damaged][next(4) as usize])
+                        .collect();
+                    let known_sequence = (0..(1 + next(4))).map(|_| 1 + next(4) as u16).collect();
+                    Row { tiles, known_sequence }
+                })
+                .collect()
+        }
+
+        #[test]
+        fn arrangements_match_examples() {
+            let row = Row::parse("???.### 1,1,3", 1).unwrap();
+
+            let arrangements: Vec<_> = row.arrangements().collect();
+
+            assert_eq!(arrangements, vec![vec![Damaged, Operational, Damaged, Operational, Damaged, Damaged, Damaged]]);
+        }
+
+        #[test]
+        fn counts_and_arrangements_match_brute_force() {
+            for row in random_rows(500) {
+                let expected = brute_force_arrangements(&row);
+
+                let arrangements: Vec<_> = row.arrangements().collect();
+                let unique: HashSet<_> = arrangements.iter().cloned().collect();
+
+                assert_eq!(row.find_possible_combinations(), expected.len(), "{:?}", row);
+                assert_eq!(arrangements.len(), unique.len(), "{:?}", row);
+                assert_eq!(unique, expected, "{:?}", row);
+            }
         }
     }
 }
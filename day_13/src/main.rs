@@ -1,156 +1,136 @@
-use std::{fmt::Debug, io::{BufRead, stdin}};
+use std::fmt::Debug;
+use std::io::{Read, stdin};
+use aoc_shared::coords2d::Coords2D;
+use aoc_shared::map2d::Map2D;
 
 fn main() {
-    let maps = read_maps(&mut stdin().lock());
+    let mut input = String::new();
+    stdin().lock().read_to_string(&mut input).unwrap();
+    let maps = read_maps(&input);
     println!("Read {} map/s", maps.len());
 
-    let mut sum = 0;
-    for map in maps.iter() {
-        if let Some(vertical_value) = map.find_vertical_mirror_index() {
-            sum += vertical_value;
-        } else if let Some(horizontal_value) = map.find_horizontal_mirror_index() {
-            sum += horizontal_value * 100;
-        } else {
-            panic!("No vertical or horizontal");
+    for (part, smudges) in [(1, 0), (2, 1)] {
+        let mut sum = 0;
+        for (i, map) in maps.iter().enumerate() {
+            let reflections = find_reflections(map, smudges);
+            if reflections.is_empty() {
+                println!("Map {} has no reflection with {} smudge/s", i + 1, smudges);
+            }
+            sum += reflections.iter().map(Reflection::summary).sum::<usize>();
         }
+        println!("Part {part}: {sum}");
     }
-
-    println!("{sum}");
 }
 
-fn read_maps(input: &mut impl BufRead) -> Vec<Map> {
-    let mut maps = vec![];
-    while let Some(map) = Map::try_from(input) {
-        maps.push(map);
-    }
-    maps
-}
+type Map = Map2D<Tile>;
 
-struct Point2D(usize, usize);
+fn read_maps(input: &str) -> Vec<Map> {
+    input
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|block| Map::try_from_reader(&mut block.trim().as_bytes()))
+        .map(|result| result.unwrap())
+        .collect()
+}
 
-struct Map {
-    tiles: Vec<Tile>,
-    width: usize,
-    height: usize,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    /// A vertical line, with `position` columns to its left.
+    Vertical,
+    /// A horizontal line, with `position` rows above it.
+    Horizontal,
 }
 
-impl Map {
-    fn try_from(input: &mut impl BufRead) -> Option<Self> {
-        let mut lines = input.lines().flatten().take_while(|l| !l.is_empty());
-        let first_line = match lines.next() {
-            Some(line) => line,
-            None => return None,
-        };
+#[derive(Debug, PartialEq, Eq)]
+struct Reflection {
+    axis: Axis,
+    position: usize,
+    /// Tiles that have to be flipped for the reflection to be perfect, on the left or upper side.
+    smudges: Vec<Coords2D>,
+}
 
-        let mut tiles: Vec<_> = first_line.chars().map(|c| Tile::try_from(c).unwrap()).collect();
-        let width = tiles.len();
-        let mut height = 1;
-        for line in lines {
-            tiles.extend(line.chars().map(|c| Tile::try_from(c).unwrap()));
-            height += 1;
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Vertical => self.position,
+            Axis::Horizontal => self.position * 100,
         }
-        Some(Self {
-            tiles,
-            width,
-            height,
-        })
-    }
-
-    fn get_index(&self, point: Point2D) -> usize {
-        point.0 + (self.width * point.1)
     }
+}
 
-    fn get(&self, point: Point2D) -> Option<&Tile> {
-        if point.0 >= self.width || point.1 >= self.height {
-            return None
-        }
-        self.tiles.get(self.get_index(point))
+/// Finds every reflection line of the map, on both axes, that needs exactly `smudges` tiles to be
+/// flipped to be perfect.
+fn find_reflections(map: &Map, smudges: usize) -> Vec<Reflection> {
+    let (rows, columns) = line_masks(map);
+    let mut reflections = vec![];
+
+    for (position, differences) in find_mirror_positions(&columns, smudges) {
+        let smudges = differences
+            .into_iter()
+            .flat_map(|(x, mask)| bits(mask).map(move |y| Coords2D(x, y)))
+            .collect();
+        reflections.push(Reflection { axis: Axis::Vertical, position, smudges });
     }
-
-    fn find_vertical_mirror_index(&self) -> Option<usize> {
-        for i in 1..self.width {
-            if self.is_vertical_mirror_index(i) {
-                return Some(i);
-            }
-        }
-        None
+    for (position, differences) in find_mirror_positions(&rows, smudges) {
+        let smudges = differences
+            .into_iter()
+            .flat_map(|(y, mask)| bits(mask).map(move |x| Coords2D(x, y)))
+            .collect();
+        reflections.push(Reflection { axis: Axis::Horizontal, position, smudges });
     }
+    reflections
+}
 
-    fn is_vertical_mirror_index(&self, i: usize) -> bool {
-        let mut diffs = 0;
-        for (left, right) in Self::mirror_iter(i, self.width) {
-            diffs += self.vertical_check(left, right);
-            if diffs > 1 {
-                return false;
+/// The tiles of a row or column, with a bit set for every rock and as many 64 bit words as the
+/// line needs.
+type LineMask = Vec<u64>;
+
+/// Packs every row and column of the map into a bitmask where rocks are set.
+fn line_masks(map: &Map) -> (Vec<LineMask>, Vec<LineMask>) {
+    let mut rows = vec![vec![0; map.width().div_ceil(64)]; map.height()];
+    let mut columns = vec![vec![0; map.height().div_ceil(64)]; map.width()];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (x, column) in columns.iter_mut().enumerate() {
+            if map.get(Coords2D(x, y)) == Some(&Tile::Rock) {
+                row[x / 64] |= 1 << (x % 64);
+                column[y / 64] |= 1 << (y % 64);
             }
         }
-        diffs == 1
     }
+    (rows, columns)
+}
 
-    fn vertical_check(&self, i1: usize, i2: usize) -> usize {
+/// Returns the positions where the lines mirror each other with exactly `smudges` differing
+/// bits, along with the mask of differences of each line before the mirror.
+fn find_mirror_positions(lines: &[LineMask], smudges: usize) -> Vec<(usize, Vec<(usize, LineMask)>)> {
+    let mut positions = vec![];
+    for position in 1..lines.len() {
         let mut diffs = 0;
-        for y in 0..self.height {
-            let left_tile = self.get(Point2D(i1, y));
-            let right_tile = self.get(Point2D(i2, y));
-            if left_tile.is_none() || right_tile.is_none() {
-                return diffs;
+        let mut differences = vec![];
+        for (before, after) in (0..position).rev().zip(position..lines.len()) {
+            let difference: LineMask = lines[before].iter().zip(&lines[after]).map(|(a, b)| a ^ b).collect();
+            let count = difference.iter().map(|word| word.count_ones() as usize).sum::<usize>();
+            if count != 0 {
+                diffs += count;
+                differences.push((before, difference));
             }
-
-            if *left_tile.unwrap() != *right_tile.unwrap() {
-                diffs += 1;
+            if diffs > smudges {
+                break;
             }
         }
-        return diffs;
-    }
-
-    fn find_horizontal_mirror_index(&self) -> Option<usize> {
-        for i in 1..self.height {
-            if self.is_horizontal_mirror_index(i) {
-                return Some(i);
-            }
-        }
-        None
-    }
-
-    fn is_horizontal_mirror_index(&self, i: usize) -> bool {
-        let mut diffs = 0;
-        for (upper, bottom) in Self::mirror_iter(i, self.height) {
-            diffs += self.horizontal_check(upper, bottom);
-            if diffs > 1 {
-                return false;
-            }
+        if diffs == smudges {
+            positions.push((position, differences));
         }
-        diffs == 1
-    }
-
-    fn horizontal_check(&self, i1: usize, i2: usize) -> usize {
-        let mut diffs = 0;
-        for x in 0..self.width {
-            let tile1 = self.get(Point2D(x, i1));
-            let tile2 = self.get(Point2D(x, i2));
-            if tile1.is_none() || tile2.is_none() {
-                return diffs;
-            }
-
-            if *tile1.unwrap() != *tile2.unwrap() {
-                diffs += 1;
-            }
-        }
-        return diffs;
     }
+    positions
+}
 
-    fn mirror_iter(start: usize, width: usize) -> impl Iterator<Item = (usize, usize)> {
-        (0..(width - start))
-            .map(move |i| {
-                let right = start + i;
-                if i < start && right < width {
-                    Some((start - i - 1, right))
-                } else {
-                    None
-                }
-            })
-            .flatten()
-    }
+fn bits(mask: LineMask) -> impl Iterator<Item = usize> {
+    mask.into_iter().enumerate().flat_map(|(word_index, word)| {
+        (0..u64::BITS as usize)
+            .filter(move |i| word & (1 << i) != 0)
+            .map(move |i| word_index * u64::BITS as usize + i)
+    })
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -179,3 +159,84 @@ impl TryFrom<char> for Tile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aoc_shared::coords2d::Coords2D;
+    use crate::{Axis, Reflection, find_reflections, read_maps};
+
+    const EXAMPLE: &str = "\
+#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#
+";
+
+    #[test]
+    fn finds_perfect_reflections() {
+        let maps = read_maps(EXAMPLE);
+
+        let first = find_reflections(&maps[0], 0);
+        let second = find_reflections(&maps[1], 0);
+
+        assert_eq!(first, vec![Reflection { axis: Axis::Vertical, position: 5, smudges: vec![] }]);
+        assert_eq!(second, vec![Reflection { axis: Axis::Horizontal, position: 4, smudges: vec![] }]);
+    }
+
+    #[test]
+    fn finds_reflections_with_one_smudge() {
+        let maps = read_maps(EXAMPLE);
+
+        let first = find_reflections(&maps[0], 1);
+        let second = find_reflections(&maps[1], 1);
+
+        assert_eq!(first, vec![Reflection { axis: Axis::Horizontal, position: 3, smudges: vec![Coords2D(0, 0)] }]);
+        assert_eq!(second, vec![Reflection { axis: Axis::Horizontal, position: 1, smudges: vec![Coords2D(4, 0)] }]);
+    }
+
+    #[test]
+    fn summarises_both_parts() {
+        let maps = read_maps(EXAMPLE);
+
+        let summarise = |smudges| -> usize {
+            maps.iter().flat_map(|m| find_reflections(m, smudges)).map(|r| r.summary()).sum()
+        };
+
+        assert_eq!(summarise(0), 405);
+        assert_eq!(summarise(1), 400);
+    }
+
+    #[test]
+    fn handles_maps_bigger_than_a_word() {
+        // 130 columns mirrored around the middle, over 70 rows that are all different
+        let mut rows: Vec<String> = (0..70)
+            .map(|y| {
+                let half: String = (0..65).map(|x| if (x * 7 + y * 13) % 5 == 0 || x == y { '#' } else { '.' }).collect();
+                half.clone() + &half.chars().rev().collect::<String>()
+            })
+            .collect();
+        let perfect = read_maps(&rows.join("\n"));
+        let flipped = if rows[68].as_bytes()[100] == b'#' { "." } else { "#" };
+        rows[68].replace_range(100..101, flipped);
+        let smudged = read_maps(&rows.join("\n"));
+
+        let vertical = |map, smudges| find_reflections(map, smudges)
+            .into_iter()
+            .filter(|r| r.axis == Axis::Vertical)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vertical(&perfect[0], 0), vec![Reflection { axis: Axis::Vertical, position: 65, smudges: vec![] }]);
+        assert_eq!(vertical(&smudged[0], 1), vec![Reflection { axis: Axis::Vertical, position: 65, smudges: vec![Coords2D(29, 68)] }]);
+    }
+}
@@ -1,8 +1,10 @@
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use aoc_shared::coords2d::Coords2D;
+use aoc_shared::cycle::find_cycle;
+use aoc_shared::direction::Direction;
+use aoc_shared::map2d::{Map2D, ParseMapError};
 
 fn main() {
     let path = std::env::current_dir().unwrap().join("day_14/input.txt");
@@ -11,12 +13,12 @@ fn main() {
     let file = File::open(path).unwrap();
     let mut reader = BufReader::new(file);
 
-    let mut map = Map::try_from(&mut reader).unwrap();
+    let map = Map::try_from_reader(&mut reader).unwrap_or_else(|err| panic!("{}", err));
     println!("Starting map:");
     println!("{}", &map);
     println!();
 
-    // part_1(map);
+    part_1(map.clone());
     part_2(map);
 }
 
@@ -30,89 +32,80 @@ fn part_1(mut map: Map) {
     println!("Load: {}", load);
 }
 
-fn part_2(mut map: Map) {
+fn part_2(map: Map) {
     const CYCLES: usize = 1000000000;
-    const ITERATIONS: usize = 1000;
-    
-    // I was lazy to make an algorithm (And probably in a non foolproof way) that would
-    // find the cycle and extrapolate from 1000000000.
-    // This worked for my input.
-    // However, probably storing the values in a vector is a better way.
-    
-    // (last_seen_index, estimated_length)
-    let mut repeating_values_length = HashMap::<usize, (usize, Option<usize>)>::default();
-    for i in 1..=ITERATIONS {
-        map.tilt(Direction::North);
-        map.tilt(Direction::West);
-        map.tilt(Direction::South);
-        map.tilt(Direction::East);
-
-        let cur_value = map.calculate_north_load();
-        match repeating_values_length.entry(cur_value) {
-            Entry::Occupied(mut o) => {
-                let previous = *o.get();
-                o.insert((i, Some(i - previous.0)));
-            },
-            Entry::Vacant(mut v) => {
-                v.insert((i, None));
-            },
-        }
-        // print!("{load}, ");
-    }
-    println!();
-    println!("{}", &map);
+
+    let cycle = find_cycle(map, |map| {
+        let mut map = map.clone();
+        map.spin();
+        map
+    });
+    println!("Cycle of length {} found after {} spin cycle/s", cycle.length(), cycle.start());
+
+    let map = cycle.state_at(CYCLES);
+    println!("{}", map);
     println!();
-    let max_length = repeating_values_length
-        .values()
-        .map(|x| x.1)
-        .flatten()
-        .max();
-    // println!("Values:");
-    // println!("{:#?}", repeating_values_length);
-    println!("Max length: {:?}", max_length);
-    
-    let estimated_cycle = max_length.unwrap();
-    let value = repeating_values_length
-        .iter()
-        .find(|(value, &info)| {
-            ((CYCLES - info.0) % estimated_cycle) == 0
-        })
-        .map(|v| v.0);
-    
-    println!("Estimated value: {:?}", value);
-    // let other_values: Vec<_> = repeating_values_length.keys().collect();
-    // println!("Other values:\n{:#?}", other_values);
+    println!("Load after {} spin cycles: {}", CYCLES, map.calculate_north_load());
 }
 
-type Tile = char;
-const ROUND_ROCK: Tile = 'O';
-const CUBE_ROCK: Tile = '#';
-const GROUND: Tile = '.';
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tile {
+    Ground,
+    RoundRock,
+    CubeRock,
+}
 
-#[derive(Debug, Copy, Clone, Default)]
-struct Point2D(usize, usize);
+impl TryFrom<char> for Tile {
+    type Error = char;
 
-#[derive(Debug, Copy, Clone)]
-enum Direction {
-    North,
-    East,
-    South,
-    West,
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '.' => Ok(Self::Ground),
+            'O' => Ok(Self::RoundRock),
+            '#' => Ok(Self::CubeRock),
+            _ => Err(value),
+        }
+    }
 }
 
-#[derive(Clone)]
+#[derive(Debug)]
+enum ReadMapError {
+    Empty,
+    Parse(ParseMapError<Tile>),
+}
+
+impl Display for ReadMapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the input has no tiles"),
+            Self::Parse(err) => write!(f, "invalid map: {:?}", err),
+        }
+    }
+}
+
+/// The platform as bitmasks of `words_per_row` words for each row, where bit `x % 64` of word
+/// `x / 64` of a row is the tile at column `x`.
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Map {
-    tiles: Vec<Tile>,
+    round_rocks: Vec<u64>,
+    cube_rocks: Vec<u64>,
     width: usize,
-    height: usize,
+    words_per_row: usize,
 }
 
 impl Display for Map {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row_i in 0..self.height {
-            let start_i = row_i * self.width;
-            for tile in &self.tiles[start_i..(start_i + self.width)] {
-                write!(f, "{}", tile)?;
+        for y in 0..self.height() {
+            for x in 0..self.width {
+                let (word, bit) = self.bit_position(x, y);
+                let ch = if self.round_rocks[word] & bit != 0 {
+                    'O'
+                } else if self.cube_rocks[word] & bit != 0 {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", ch)?;
             }
             writeln!(f)?;
         }
@@ -120,133 +113,321 @@ impl Display for Map {
     }
 }
 
-impl Map {
-    fn try_from(input: &mut impl BufRead) -> Option<Self> {
-        let mut lines = input.lines().flatten().take_while(|l| !l.is_empty());
-        let first_line = match lines.next() {
-            Some(line) => line,
-            None => return None,
-        };
-        let mut tiles: Vec<_> = first_line.chars().skip(1).collect();
-        let width = tiles.len();
-        let mut expected_length = width;
+/// The positions of the set bits of a word, from either end.
+struct Bits(u64);
 
-        let mut height = 1;
-        for line in lines {
-            tiles.extend(line.chars());
-            height += 1;
+impl Iterator for Bits {
+    type Item = usize;
 
-            expected_length += width;
-            assert_eq!(tiles.len(), expected_length);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
         }
-        Some(Self {
-            tiles,
-            width,
-            height,
-        })
+        let bit = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(bit)
     }
+}
 
-    fn get_index(&self, point: Point2D) -> Option<usize> {
-        let index = point.0 + (self.width * point.1);
-        if index < self.tiles.len() {
-            Some(index)
-        } else {
-            None
+impl DoubleEndedIterator for Bits {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
         }
+        let bit = (u64::BITS - 1 - self.0.leading_zeros()) as usize;
+        self.0 &= !(1 << bit);
+        Some(bit)
     }
+}
 
-    fn get(&self, point: Point2D) -> Option<&Tile> {
-        self.get_index(point).map(|i| self.tiles.get(i)).flatten()
+impl Map {
+    fn try_from_reader(reader: &mut impl BufRead) -> Result<Self, ReadMapError> {
+        let tiles = Map2D::<Tile>::try_from_reader(reader)
+            .ok_or(ReadMapError::Empty)?
+            .map_err(ReadMapError::Parse)?;
+        Self::try_from_tiles(&tiles)
     }
-    
-    fn calculate_north_load(&self) -> usize {
-        let mut load = 0;
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let point = Point2D(x, y);
-                let tile = self.get(point).unwrap();
-                if *tile != ROUND_ROCK {
-                    continue;
-                }
 
-                let distance = self.height - y;
-                load += distance;
+    fn try_from_tiles(tiles: &Map2D<Tile>) -> Result<Self, ReadMapError> {
+        let width = tiles.width();
+        if width == 0 {
+            return Err(ReadMapError::Empty);
+        }
+
+        let words_per_row = width.div_ceil(u64::BITS as usize);
+        let mut map = Self {
+            round_rocks: vec![0; words_per_row * tiles.height()],
+            cube_rocks: vec![0; words_per_row * tiles.height()],
+            width,
+            words_per_row,
+        };
+        for y in 0..tiles.height() {
+            for x in 0..width {
+                let (word, bit) = map.bit_position(x, y);
+                match tiles.get(Coords2D(x, y)) {
+                    Some(Tile::RoundRock) => map.round_rocks[word] |= bit,
+                    Some(Tile::CubeRock) => map.cube_rocks[word] |= bit,
+                    _ => {},
+                }
             }
         }
-        load
+        Ok(map)
     }
 
+    fn height(&self) -> usize {
+        self.round_rocks.len() / self.words_per_row
+    }
+
+    /// The index of the word holding the tile, and the mask of its bit inside of it.
+    fn bit_position(&self, x: usize, y: usize) -> (usize, u64) {
+        let bits = u64::BITS as usize;
+        (y * self.words_per_row + x / bits, 1 << (x % bits))
+    }
+
+    /// The columns of every rock in the row, round or cube, from left to right.
+    fn rocks_in_row(&self, y: usize) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        let words = y * self.words_per_row..(y + 1) * self.words_per_row;
+        self.round_rocks[words.clone()]
+            .iter()
+            .zip(&self.cube_rocks[words])
+            .enumerate()
+            .flat_map(|(i, (round, cubes))| Bits(round | cubes).map(move |bit| i * u64::BITS as usize + bit))
+    }
+
+    fn calculate_north_load(&self) -> usize {
+        self.round_rocks
+            .chunks(self.words_per_row)
+            .enumerate()
+            .map(|(y, row)| row.iter().map(|word| word.count_ones() as usize).sum::<usize>() * (self.height() - y))
+            .sum()
+    }
+
+    /// Tilts the map north, west, south and then east.
+    fn spin(&mut self) {
+        self.tilt(Direction::North);
+        self.tilt(Direction::West);
+        self.tilt(Direction::South);
+        self.tilt(Direction::East);
+    }
+
+    /// Walks the rocks of every line starting from the edge the round ones roll towards, keeping
+    /// track of where the next one lands, so each rock is only visited once.
     fn tilt(&mut self, direction: Direction) {
-        let span = match direction {
-            Direction::North | Direction::South => self.width,
-            Direction::East | Direction::West => self.height,
+        let (width, height) = (self.width, self.height());
+        let mut settled = vec![0; self.round_rocks.len()];
+        match direction {
+            Direction::North | Direction::South => {
+                let towards_top = direction == Direction::North;
+                // Rows from the edge to the first free tile of each column
+                let mut next_free = vec![0; width];
+                for i in 0..height {
+                    let y = if towards_top { i } else { height - 1 - i };
+                    for x in self.rocks_in_row(y) {
+                        let (word, bit) = self.bit_position(x, y);
+                        if self.cube_rocks[word] & bit != 0 {
+                            next_free[x] = i + 1;
+                        } else {
+                            let landing = if towards_top { next_free[x] } else { height - 1 - next_free[x] };
+                            let (word, bit) = self.bit_position(x, landing);
+                            settled[word] |= bit;
+                            next_free[x] += 1;
+                        }
+                    }
+                }
+            },
+            Direction::West | Direction::East => {
+                let towards_left = direction == Direction::West;
+                for y in 0..height {
+                    // Columns from the edge to the first free tile of the row
+                    let mut next_free = 0;
+                    let mut settle = |x: usize| {
+                        let (word, bit) = self.bit_position(x, y);
+                        if self.cube_rocks[word] & bit != 0 {
+                            next_free = if towards_left { x } else { width - 1 - x } + 1;
+                        } else {
+                            let landing = if towards_left { next_free } else { width - 1 - next_free };
+                            let (word, bit) = self.bit_position(landing, y);
+                            settled[word] |= bit;
+                            next_free += 1;
+                        }
+                    };
+                    if towards_left {
+                        self.rocks_in_row(y).for_each(&mut settle);
+                    } else {
+                        self.rocks_in_row(y).rev().for_each(&mut settle);
+                    }
+                }
+            },
+        }
+        self.round_rocks = settled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use aoc_shared::cycle::find_cycle;
+    use aoc_shared::direction::Direction;
+    use aoc_shared::rng::{random_grid, random_grids, Lcg};
+    use crate::{Map, ReadMapError};
+
+    const EXAMPLE: &str = "\
+O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+
+    fn parse_map(s: &str) -> Map {
+        Map::try_from_reader(&mut s.as_bytes()).unwrap()
+    }
+
+    /// Tilts one tile at a time on a plain grid of chars, to check the bitboard against.
+    fn naive_tilt(grid: &mut [Vec<char>], direction: Direction) {
+        let (dx, dy): (isize, isize) = match direction {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
         };
-        for i in 0..span {
-            let scan_line = ScanLine::new(&self, i, direction);
-            let mut cur_sequence = 0;
-            for j in 0..scan_line.len() {
-                let point = scan_line.get_point(j);
-                // println!("Scanning {:?}", point);
-                let index = self.get_index(point).unwrap();
-                let tile = self.tiles[index];
-                match tile {
-                    CUBE_ROCK => {
-                        self.set_rocks(&scan_line, j, cur_sequence);
-                        cur_sequence = 0;
-                    },
-                    ROUND_ROCK => {
-                        cur_sequence += 1;
-                        self.tiles[index] = GROUND;
-                    },
-                    _ => {}
+        let height = grid.len() as isize;
+        let width = grid[0].len() as isize;
+        let mut moved_any = true;
+        while moved_any {
+            moved_any = false;
+            for y in 0..height {
+                for x in 0..width {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                        continue;
+                    }
+                    if grid[y as usize][x as usize] == 'O' && grid[ny as usize][nx as usize] == '.' {
+                        grid[y as usize][x as usize] = '.';
+                        grid[ny as usize][nx as usize] = 'O';
+                        moved_any = true;
+                    }
                 }
             }
-            
-            if cur_sequence > 0 {
-                self.set_rocks(&scan_line, scan_line.len(), cur_sequence);
-            }
         }
     }
 
-    fn set_rocks(&mut self, scan_line: &ScanLine, until: usize, amount: usize) {
-        for i in (until - amount)..until {
-            let point = scan_line.get_point(i);
-            let index = self.get_index(point).unwrap();
-            self.tiles[index] = ROUND_ROCK;
+    fn tile(rng: &mut Lcg) -> char {
+        match rng.below(10) {
+            0..=1 => 'O',
+            2 => '#',
+            _ => '.',
         }
     }
-}
 
-struct ScanLine {
-    len: usize,
-    number: usize,
-    direction: Direction,
-}
+    fn generate_map(width: usize, height: usize, seed: u64) -> String {
+        random_grid(width, height, seed, tile)
+    }
 
-impl ScanLine {
-    fn new(map: &Map, number: usize, direction: Direction) -> Self {
-        let len = match direction {
-            Direction::North | Direction::South => map.height,
-            Direction::East | Direction::West => map.width,
-        };
-        Self {
-            len,
-            number,
-            direction,
+    #[test]
+    fn reads_first_column() {
+        let map = parse_map(EXAMPLE);
+
+        assert_eq!(map.to_string(), format!("{}\n", EXAMPLE));
+    }
+
+    #[test]
+    fn reads_input_with_byte_order_mark() {
+        let map = parse_map(&format!("\u{feff}{}", EXAMPLE));
+
+        assert_eq!(map.to_string(), format!("{}\n", EXAMPLE));
+    }
+
+    #[test]
+    fn calculates_load_after_tilting_north() {
+        let mut map = parse_map(EXAMPLE);
+
+        map.tilt(Direction::North);
+
+        assert_eq!(map.calculate_north_load(), 136);
+    }
+
+    #[test]
+    fn spins_like_the_example() {
+        let mut map = parse_map(EXAMPLE);
+        let expected = "\
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+";
+
+        map.spin();
+
+        assert_eq!(map.to_string(), expected);
+    }
+
+    #[test]
+    fn calculates_load_after_a_billion_spins() {
+        let cycle = find_cycle(parse_map(EXAMPLE), |map| {
+            let mut map = map.clone();
+            map.spin();
+            map
+        });
+
+        assert_eq!(cycle.state_at(1_000_000_000).calculate_north_load(), 64);
+    }
+
+    #[test]
+    fn tilts_like_naive_implementation() {
+        for ((width, height), input) in random_grids(&[(1, 1), (7, 3), (64, 20), (65, 9), (100, 100), (128, 40), (300, 25)], tile) {
+            let mut map = parse_map(&input);
+            let mut grid: Vec<Vec<char>> = input.lines().map(|l| l.chars().collect()).collect();
+
+            for direction in [Direction::North, Direction::West, Direction::South, Direction::East, Direction::West] {
+                map.tilt(direction);
+                naive_tilt(&mut grid, direction);
+
+                let expected: String = grid.iter().map(|l| l.iter().collect::<String>() + "\n").collect();
+                assert_eq!(map.to_string(), expected, "{}x{} tilting {:?}", width, height, direction);
+            }
         }
     }
 
-    /// Gets the point at the scan line index
-    fn get_point(&self, i: usize) -> Point2D {
-        match self.direction {
-            Direction::North => Point2D(self.number, self.len - i - 1),
-            Direction::East => Point2D(i, self.number),
-            Direction::South => Point2D(self.number, i),
-            Direction::West => Point2D(self.len - i - 1, self.number),
+    #[test]
+    fn rejects_blank_lines() {
+        assert!(matches!(Map::try_from_reader(&mut "\n\n".as_bytes()), Err(ReadMapError::Empty)));
+    }
+
+    #[test]
+    fn spins_wide_platforms() {
+        let mut map = parse_map(&generate_map(1000, 1000, 42));
+        let rocks = map.to_string().matches('O').count();
+
+        for _ in 0..3 {
+            map.spin();
         }
+
+        assert_eq!(map.to_string().matches('O').count(), rocks);
     }
-    
-    fn len(&self) -> usize {
-        self.len
+
+    #[test]
+    fn rocks_roll_across_tall_platforms_in_linear_time() {
+        // Every rock starts at the bottom and has to roll all the way up, which took quadratic
+        // time when each row was moved up one step at a time.
+        let height = 20_000;
+        let input = format!("{}{}", format!("{}\n", ".".repeat(64)).repeat(10_000), format!("{}\n", ".O".repeat(32)).repeat(10_000));
+        let mut map = parse_map(&input);
+
+        let now = Instant::now();
+        map.tilt(Direction::North);
+
+        assert!(now.elapsed() < Duration::from_secs(1), "{:?}", now.elapsed());
+        assert_eq!(map.calculate_north_load(), 32 * (height - 10_000 + 1..=height).sum::<usize>());
     }
 }
@@ -1,45 +1,55 @@
-use std::{fmt::Debug, ops::Deref};
-use array_init::array_init;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::num::ParseIntError;
 
 fn main() {
     let path = std::env::current_dir().unwrap().join("day_15/input.txt");
     println!("Opening file: {}", path.display());
     println!();
     let content = std::fs::read_to_string(path).unwrap();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let show_trace = args.iter().any(|arg| arg == "--trace");
+    let value_after = |flag: &str| {
+        args.iter().position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+            .map(|n| n.parse().unwrap_or_else(|_| panic!("{flag} expects a number")))
+    };
+    let config = HashConfig::new(value_after("--boxes").unwrap_or(256), value_after("--modulus").unwrap_or(256));
 
-    // part_1(&content);
-    part_2(&content);
+    part_1(&content);
+    match config {
+        Ok(config) => part_2(&content, config, show_trace),
+        Err(err) => println!("Invalid configuration: {}", err),
+    }
 }
 
 fn part_1(input: &str) {
-    let sum: usize = input.split(',').map(|s| holiday_hash(s) as usize).sum();
+    let sum: usize = steps(input).map(|s| holiday_hash(s) as usize).sum();
     println!("{sum}");
 }
 
-fn part_2(input: &str) {
-    let mut hashmap = HolidayHashMap::default();
-    for instruction in input.split(',') {
-        if instruction.ends_with('-') {
-            let label = instruction.trim_end_matches('-');
-            hashmap.remove_lens(label);
-        } else if let Some((label, focal_length)) = try_get_assign_instruction(instruction) {
-            hashmap.insert_lens(label, focal_length);
-        } else {
-            panic!("{}", instruction);
+fn part_2(input: &str, config: HashConfig, show_trace: bool) {
+    let on_step = |step: &str, hashmap: &HolidayHashMap| {
+        if show_trace {
+            print!("{}", describe_step(step, hashmap));
         }
+    };
+    match run(input, config, on_step) {
+        Ok(hashmap) => {
+            if show_trace {
+                println!("{:?}", &hashmap);
+            }
+            println!("{}", hashmap.calculate_focusing_power());
+        },
+        Err(err) => println!("{}", err),
     }
-    println!("{:?}", &hashmap);
-    println!("{}", hashmap.calculate_focusing_power());
-    
 }
 
-fn try_get_assign_instruction(s: &str) -> Option<(&str, u8)> {
-    let mut split = s.split('=');
-    split.next()
-        .map(|label| split
-            .next()
-            .map(|n| (label, n.parse::<u8>().expect("Focal length needs to be u8"))))
-        .flatten()
+/// Splits the initialization sequence into its steps, ignoring newlines.
+fn steps(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .split(',')
+        .map(|s| s.trim_matches(|c| c == '\n' || c == '\r'))
 }
 
 fn holiday_hash(s: &str) -> u8 {
@@ -48,75 +58,254 @@ fn holiday_hash(s: &str) -> u8 {
         value = value.wrapping_add(byte);
         value = value.wrapping_mul(17);
     }
-    // println!("{value}");
     value
 }
 
-struct HolidayHashMap([Option<Vec<Lens>>; 256]);
+/// The HASH algorithm with any modulus instead of the 256 that wrapping a `u8` gives.
+fn holiday_hash_with_modulus(s: &str, modulus: usize) -> usize {
+    let mut value = 0;
+    for byte in s.bytes() {
+        value = ((value + byte as usize) * 17) % modulus;
+    }
+    value
+}
 
-impl HolidayHashMap {
-    pub fn insert_lens(&mut self, label: &str, focal_length: u8) {
-        let position = holiday_hash(label) as usize;
-        if let Some(some_box) = self.0[position].as_mut() {
-            Self::insert_lens_in_vec(some_box, label, focal_length);
-        } else {
-            self.0[position] = Some(vec![Lens::new(label, focal_length)])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HashConfig {
+    box_count: usize,
+    modulus: usize,
+}
+
+impl Default for HashConfig {
+    fn default() -> Self {
+        Self { box_count: 256, modulus: 256 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HashConfigError {
+    NoBoxes,
+    ZeroModulus,
+}
+
+impl Display for HashConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoBoxes => write!(f, "there must be at least one box"),
+            Self::ZeroModulus => write!(f, "the hash modulus can't be 0"),
         }
     }
+}
+
+impl HashConfig {
+    fn new(box_count: usize, modulus: usize) -> Result<Self, HashConfigError> {
+        if box_count == 0 {
+            return Err(HashConfigError::NoBoxes);
+        }
+        if modulus == 0 {
+            return Err(HashConfigError::ZeroModulus);
+        }
+        Ok(Self { box_count, modulus })
+    }
+
+    fn box_for(&self, label: &str) -> usize {
+        holiday_hash_with_modulus(label, self.modulus) % self.box_count
+    }
+}
 
-    // We could have a LensBox struct that handles this
-    fn insert_lens_in_vec(vec: &mut Vec<Lens>, label: &str, focal_length: u8) {
-        let lens_to_replace = vec.iter_mut().find(|lens| lens.has_label(label));
-        if let Some(mut lens) = lens_to_replace {
-            lens.focal_length = focal_length;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step<'a> {
+    Remove { label: &'a str },
+    Insert { label: &'a str, focal_length: u8 },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ParseStepErrorKind {
+    EmptyLabel,
+    MissingOperation,
+    InvalidFocalLength(ParseIntError),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ParseStepError {
+    /// Position of the step in the sequence, starting from 0.
+    index: usize,
+    step: String,
+    kind: ParseStepErrorKind,
+}
+
+impl Display for ParseStepError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid step {} \"{}\": ", self.index, self.step)?;
+        match &self.kind {
+            ParseStepErrorKind::EmptyLabel => write!(f, "the label is empty"),
+            ParseStepErrorKind::MissingOperation => write!(f, "expected '-' or '='"),
+            ParseStepErrorKind::InvalidFocalLength(err) => write!(f, "invalid focal length ({})", err),
+        }
+    }
+}
+
+impl<'a> Step<'a> {
+    fn parse(s: &'a str) -> Result<Self, ParseStepErrorKind> {
+        let (label, step) = if let Some(label) = s.strip_suffix('-') {
+            (label, Step::Remove { label })
+        } else if let Some((label, focal_length)) = s.split_once('=') {
+            let focal_length = focal_length.parse().map_err(ParseStepErrorKind::InvalidFocalLength)?;
+            (label, Step::Insert { label, focal_length })
         } else {
-            vec.push(Lens::new(label, focal_length))
+            return Err(ParseStepErrorKind::MissingOperation);
+        };
+
+        if label.is_empty() {
+            return Err(ParseStepErrorKind::EmptyLabel);
         }
+        Ok(step)
     }
+}
 
-    pub fn remove_lens(&mut self, label: &str) -> bool {
-        let position = holiday_hash(label) as usize;
-        if let Some(some_box) = self.0[position].as_mut() {
-            if let Some(pos) = some_box.iter().position(|l| l.has_label(label)) {
-                some_box.remove(pos);
-                return true;
-            }
+/// Runs the initialization sequence, calling `on_step` with each step and the state right after it.
+fn run(
+    input: &str,
+    config: HashConfig,
+    mut on_step: impl FnMut(&str, &HolidayHashMap),
+) -> Result<HolidayHashMap, ParseStepError> {
+    let mut hashmap = HolidayHashMap::new(config);
+    for (index, s) in steps(input).enumerate() {
+        let step = Step::parse(s).map_err(|kind| ParseStepError { index, step: s.to_owned(), kind })?;
+        hashmap.apply(step);
+        on_step(s, &hashmap);
+    }
+    Ok(hashmap)
+}
+
+/// The state of the boxes after a step, in the same format as the puzzle's example.
+fn describe_step(step: &str, hashmap: &HolidayHashMap) -> String {
+    format!("After \"{}\":\n{}\n", step, hashmap)
+}
+
+struct HolidayHashMap {
+    config: HashConfig,
+    boxes: Vec<LensBox>,
+}
+
+impl HolidayHashMap {
+    fn new(config: HashConfig) -> Self {
+        Self {
+            config,
+            boxes: (0..config.box_count).map(|_| LensBox::default()).collect(),
+        }
+    }
+
+    pub fn apply(&mut self, step: Step) {
+        match step {
+            Step::Remove { label } => {
+                self.remove_lens(label);
+            },
+            Step::Insert { label, focal_length } => self.insert_lens(label, focal_length),
         }
-        return false;
+    }
+
+    pub fn insert_lens(&mut self, label: &str, focal_length: u8) {
+        let position = self.config.box_for(label);
+        self.boxes[position].insert(label, focal_length);
+    }
+
+    pub fn remove_lens(&mut self, label: &str) -> bool {
+        let position = self.config.box_for(label);
+        self.boxes[position].remove(label)
     }
 
     pub fn calculate_focusing_power(&self) -> usize {
         let mut sum = 0;
-        for (box_i, cur_box) in self.0.iter().enumerate() {
-            if let Some(b) = cur_box {
-                for (lens_i, lens) in b.iter().enumerate() {
-                    let power = (box_i + 1) * (lens_i + 1) * lens.focal_length as usize;
-                    sum += power;
-                }
+        for (box_i, cur_box) in self.boxes.iter().enumerate() {
+            for (lens_i, lens) in cur_box.lenses().enumerate() {
+                let power = (box_i + 1) * (lens_i + 1) * lens.focal_length as usize;
+                sum += power;
             }
         }
         sum
     }
 }
 
-impl Default for HolidayHashMap {
-    fn default() -> Self {
-        Self(array_init(|_| None))
+impl Display for HolidayHashMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, cur_box) in self.boxes.iter().enumerate() {
+            if cur_box.is_empty() {
+                continue;
+            }
+            write!(f, "Box {}:", i)?;
+            for lens in cur_box.lenses() {
+                write!(f, " {:?}", lens)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
 impl Debug for HolidayHashMap {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (i, cur_box) in self.0.iter().enumerate() {
-            match cur_box {
-                Some(b) if b.len() > 0 => writeln!(f, "Box {}: {:?}", i, b)?,
-                _ => {},
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, cur_box) in self.boxes.iter().enumerate() {
+            if !cur_box.is_empty() {
+                let lenses: Vec<_> = cur_box.lenses().collect();
+                writeln!(f, "Box {}: {:?}", i, lenses)?;
             }
         }
         Ok(())
     }
 }
 
+/// Lenses in insertion order, where removed lenses leave a tombstone behind so no other lens
+/// has to move. The slots are compacted once tombstones take up more than half of them.
+#[derive(Default)]
+struct LensBox {
+    slots: Vec<Option<Lens>>,
+    slot_by_label: HashMap<String, usize>,
+}
+
+impl LensBox {
+    fn insert(&mut self, label: &str, focal_length: u8) {
+        if let Some(&slot) = self.slot_by_label.get(label) {
+            if let Some(lens) = self.slots[slot].as_mut() {
+                lens.focal_length = focal_length;
+            }
+        } else {
+            self.slot_by_label.insert(label.to_owned(), self.slots.len());
+            self.slots.push(Some(Lens::new(label, focal_length)));
+        }
+    }
+
+    fn remove(&mut self, label: &str) -> bool {
+        let Some(slot) = self.slot_by_label.remove(label) else {
+            return false;
+        };
+        self.slots[slot] = None;
+        if self.slots.len() > 2 * self.len() {
+            self.compact();
+        }
+        true
+    }
+
+    fn compact(&mut self) {
+        self.slots.retain(Option::is_some);
+        for (slot, lens) in self.slots.iter().flatten().enumerate() {
+            *self.slot_by_label.get_mut(&lens.label).unwrap() = slot;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.slot_by_label.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lenses(&self) -> impl Iterator<Item = &Lens> {
+        self.slots.iter().flatten()
+    }
+}
+
 struct Lens {
     label: String, // This can be transformed into a &'a, not going to complicate the code like this
     focal_length: u8,
@@ -127,13 +316,185 @@ impl Lens {
         Self { label: label.to_owned(), focal_length }
     }
 
-    fn has_label(&self, label: &str) -> bool {
-        self.label == label
-    }
 }
 
 impl Debug for Lens {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "[{} {}]", &self.label, self.focal_length)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use aoc_shared::rng::Lcg;
+    use crate::{HashConfig, HashConfigError, LensBox, ParseStepErrorKind, describe_step, holiday_hash, holiday_hash_with_modulus, run, ParseStepError, Step};
+
+    const EXAMPLE: &str = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7\n";
+
+    #[test]
+    fn hashes_like_the_example() {
+        assert_eq!(holiday_hash("HASH"), 52);
+        assert_eq!(holiday_hash_with_modulus("HASH", 256), 52);
+    }
+
+    #[test]
+    fn calculates_focusing_power() {
+        let hashmap = run(EXAMPLE, HashConfig::default(), |_, _| {}).unwrap();
+
+        assert_eq!(hashmap.calculate_focusing_power(), 145);
+    }
+
+    fn trace(input: &str, config: HashConfig) -> Result<String, ParseStepError> {
+        let mut trace = String::new();
+        run(input, config, |step, hashmap| trace += &describe_step(step, hashmap))?;
+        Ok(trace)
+    }
+
+    #[test]
+    fn traces_like_the_example() {
+        let result = trace("rn=1,cm-,qp=3,cm=2,qp-", HashConfig::default()).unwrap();
+
+        let expected = "\
+After \"rn=1\":
+Box 0: [rn 1]
+
+After \"cm-\":
+Box 0: [rn 1]
+
+After \"qp=3\":
+Box 0: [rn 1]
+Box 1: [qp 3]
+
+After \"cm=2\":
+Box 0: [rn 1] [cm 2]
+Box 1: [qp 3]
+
+After \"qp-\":
+Box 0: [rn 1] [cm 2]
+
+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn uses_configured_box_count() {
+        let config = HashConfig::new(1, 256).unwrap();
+
+        let result = trace("rn=1,qp=3", config).unwrap();
+
+        assert_eq!(result, "After \"rn=1\":\nBox 0: [rn 1]\n\nAfter \"qp=3\":\nBox 0: [rn 1] [qp 3]\n\n");
+    }
+
+    #[test]
+    fn rejects_zero_box_count_and_modulus() {
+        assert_eq!(HashConfig::new(0, 256), Err(HashConfigError::NoBoxes));
+        assert_eq!(HashConfig::new(256, 0), Err(HashConfigError::ZeroModulus));
+    }
+
+    #[test]
+    fn parses_steps() {
+        assert_eq!(Step::parse("cm-"), Ok(Step::Remove { label: "cm" }));
+        assert_eq!(Step::parse("qp=3"), Ok(Step::Insert { label: "qp", focal_length: 3 }));
+        assert_eq!(Step::parse("=3"), Err(ParseStepErrorKind::EmptyLabel));
+        assert_eq!(Step::parse("qp"), Err(ParseStepErrorKind::MissingOperation));
+        assert!(matches!(Step::parse("qp=300"), Err(ParseStepErrorKind::InvalidFocalLength(_))));
+    }
+
+    #[test]
+    fn reports_index_of_invalid_step() {
+        let result = run("rn=1,cm-,qp=x", HashConfig::default(), |_, _| {});
+
+        let err = result.err().unwrap();
+        assert_eq!(err.index, 2);
+        assert_eq!(err.step, "qp=x");
+    }
+
+    #[test]
+    fn keeps_lenses_in_order_through_removals() {
+        let mut lens_box = LensBox::default();
+        for (i, label) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            lens_box.insert(label, i as u8);
+        }
+
+        lens_box.remove("b");
+        lens_box.remove("d");
+        lens_box.remove("a");
+        lens_box.insert("b", 9);
+        lens_box.insert("e", 7);
+
+        let lenses: Vec<_> = lens_box.lenses().map(|l| (l.label.as_str(), l.focal_length)).collect();
+        assert_eq!(lenses, vec![("c", 2), ("e", 7), ("b", 9)]);
+        assert!(!lens_box.remove("d"));
+    }
+
+    /// Generates `count` steps over a pool of labels, with roughly a third of them being removals.
+    fn generate_steps(count: usize, labels: usize) -> String {
+        let mut rng = Lcg::new(15);
+        let mut next = move |bound: u64| rng.below(bound);
+        let mut steps = Vec::with_capacity(count);
+        for _ in 0..count {
+            let label = format!("l{}", next(labels as u64));
+            if next(3) == 0 {
+                steps.push(format!("{}-", label));
+            } else {
+                steps.push(format!("{}={}", label, 1 + next(9)));
+            }
+        }
+        steps.join(",")
+    }
+
+    /// Same boxes as the puzzle describes them, with linear scans, to check the slots against.
+    fn naive_focusing_power(input: &str) -> usize {
+        let mut boxes: Vec<Vec<(String, u8)>> = vec![vec![]; 256];
+        for step in input.split(',') {
+            match Step::parse(step).unwrap() {
+                Step::Remove { label } => {
+                    let lenses = &mut boxes[holiday_hash(label) as usize];
+                    lenses.retain(|(l, _)| l != label);
+                },
+                Step::Insert { label, focal_length } => {
+                    let lenses = &mut boxes[holiday_hash(label) as usize];
+                    match lenses.iter_mut().find(|(l, _)| l == label) {
+                        Some(lens) => lens.1 = focal_length,
+                        None => lenses.push((label.to_owned(), focal_length)),
+                    }
+                },
+            }
+        }
+        boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(box_i, lenses)| lenses
+                .iter()
+                .enumerate()
+                .map(move |(lens_i, lens)| (box_i + 1) * (lens_i + 1) * lens.1 as usize))
+            .sum()
+    }
+
+    #[test]
+    fn matches_naive_boxes() {
+        let input = generate_steps(100_000, 2_000);
+
+        let hashmap = run(&input, HashConfig::default(), |_, _| {}).unwrap();
+
+        assert_eq!(hashmap.calculate_focusing_power(), naive_focusing_power(&input));
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored --nocapture"]
+    fn benchmark_ten_million_steps() {
+        let input = generate_steps(10_000_000, 100_000);
+
+        let now = Instant::now();
+        let hashmap = run(&input, HashConfig::default(), |_, _| {}).unwrap();
+        let power = hashmap.calculate_focusing_power();
+        println!("Slots: {:?} (focusing power {})", now.elapsed(), power);
+
+        let now = Instant::now();
+        let naive_power = naive_focusing_power(&input);
+        println!("Naive: {:?} (focusing power {})", now.elapsed(), naive_power);
+
+        assert_eq!(power, naive_power);
+    }
+}
@@ -1,7 +1,11 @@
 use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::BufReader;
+use aoc_shared::coords2d::Coords2D;
+use aoc_shared::direction::{Direction, DIRECTIONS};
+use aoc_shared::map2d::Map2D;
+use rayon::prelude::*;
 
 fn main() {
     let path = std::env::current_dir().unwrap().join("day_16/input.txt");
@@ -9,301 +13,607 @@ fn main() {
     println!();
     let file = File::open(path).unwrap();
     let mut reader = BufReader::new(file);
-    
-    let map = Map::try_from(&mut reader).unwrap();
+
+    let map = TileMap::try_from_reader(&mut reader).unwrap().unwrap();
     // println!("{}", map);
 
-    // part_1(&map);
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let style = if args.iter().any(|arg| arg == "--counts") {
+        RenderStyle::Counts
+    } else {
+        RenderStyle::Arrows
+    };
+
+    part_1(&map, style);
     part_2(&map);
+    if args.iter().any(|arg| arg == "--summary") {
+        println!();
+        print!("{}", summary_table(&entrance_summaries(&map)));
+    }
 }
 
-fn part_1(map: &Map) {
-    let points = map.get_beam_positions(Beam::starting());
-    map.display_visited(&points);
-    println!("{}", points.len())
+fn part_1(map: &TileMap, style: RenderStyle) {
+    let trace = trace_beam(map, Beam::starting());
+    print!("{}", render_trace(map, &trace, style));
+    println!("{}", energized_count(&trace))
 }
 
-fn part_2(map: &Map) {
-    let max = Beam::get_all_entrances_for_map(&map)
-        .map(|b| map.get_beam_positions(b).len())
+fn part_2(map: &TileMap) {
+    let max = energized_counts(map)
+        .into_iter()
+        .map(|(_, count)| count)
         .max();
     println!("{max:?}");
 }
 
-#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
-struct Vector2D(isize, isize);
+type TileMap = Map2D<Tile>;
 
-impl From<Direction> for Vector2D {
-    fn from(value: Direction) -> Self {
-        match value {
-            Direction::North => Vector2D(0, -1),
-            Direction::East => Vector2D(1, 0),
-            Direction::South => Vector2D(0, 1),
-            Direction::West => Vector2D(-1, 0),
-        }
-    }
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Tile {
+    Empty,
+    /// `/`
+    ForwardMirror,
+    /// `\`
+    BackwardMirror,
+    /// `|`
+    VerticalSplitter,
+    /// `-`
+    HorizontalSplitter,
 }
 
-#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
-struct Point2D(usize, usize);
-
-impl Point2D {
-    const ZERO: Self = Point2D(0, 0);
-    
-    fn try_move(&self, amount: Vector2D) -> Option<Self> {
-        let result = checked_add(self.0, amount.0)
-            .and_then(|x| checked_add(self.1, amount.1)
-                .and_then(|y| Some(Point2D(x, y))));
-        // println!("{:?} + {:?} = {:?}", self, amount, result);
-        return result;
-        
-        fn checked_add(a: usize, b: isize) -> Option<usize> {
-            if b >= 0 {
-                Some(a + b as usize)
-            } else {
-                let b_abs = b.abs() as usize;
-                a.checked_sub(b_abs)
-            }
+impl TryFrom<char> for Tile {
+    type Error = char;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '.' => Ok(Self::Empty),
+            '/' => Ok(Self::ForwardMirror),
+            '\\' => Ok(Self::BackwardMirror),
+            '|' => Ok(Self::VerticalSplitter),
+            '-' => Ok(Self::HorizontalSplitter),
+            _ => Err(value),
         }
     }
-    
-    fn try_move_one(&self, direction: Direction) -> Option<Self> {
-        self.try_move(Vector2D::from(direction))
-    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-enum Direction {
-    North,
-    East,
-    South,
-    West,
+impl Display for Tile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ch = match self {
+            Self::Empty => '.',
+            Self::ForwardMirror => '/',
+            Self::BackwardMirror => '\\',
+            Self::VerticalSplitter => '|',
+            Self::HorizontalSplitter => '-',
+        };
+        write!(f, "{}", ch)
+    }
 }
 
-impl Direction {
-    fn turn_left(&self) -> Self {
+impl Tile {
+    /// Whether a beam going towards `direction` hits the flat side of a splitter.
+    fn splits(&self, direction: Direction) -> bool {
         match self {
-            Self::North => Self::West,
-            Self::East => Self::North,
-            Self::South => Self::East,
-            Self::West => Self::South,
+            Self::VerticalSplitter => matches!(direction, Direction::East | Direction::West),
+            Self::HorizontalSplitter => matches!(direction, Direction::North | Direction::South),
+            _ => false,
         }
     }
 
-    fn turn_right(&self) -> Self {
+    fn split_directions(&self) -> [Direction; 2] {
         match self {
-            Self::North => Self::East,
-            Self::East => Self::South,
-            Self::South => Self::West,
-            Self::West => Self::North,
+            Self::HorizontalSplitter => [Direction::East, Direction::West],
+            _ => [Direction::North, Direction::South],
         }
     }
+
+    /// Direction a beam going towards `direction` leaves with, if it doesn't split.
+    fn reflect(&self, direction: Direction) -> Direction {
+        match (self, direction) {
+            (Self::ForwardMirror, Direction::North | Direction::South) => direction.turn_right(),
+            (Self::ForwardMirror, Direction::East | Direction::West) => direction.turn_left(),
+            (Self::BackwardMirror, Direction::North | Direction::South) => direction.turn_left(),
+            (Self::BackwardMirror, Direction::East | Direction::West) => direction.turn_right(),
+            _ => direction,
+        }
+    }
+
+    fn outgoing(&self, direction: Direction) -> impl Iterator<Item = Direction> {
+        let [first, second] = if self.splits(direction) {
+            self.split_directions().map(Some)
+        } else {
+            [Some(self.reflect(direction)), None]
+        };
+        first.into_iter().chain(second)
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Beam(Point2D, Direction);
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+struct Beam(Coords2D, Direction);
 
 impl Beam {
     fn starting() -> Self {
-        Self(Point2D::ZERO, Direction::East)
+        Self(Coords2D::ZERO, Direction::East)
     }
-    
-    fn try_move_one(&self) -> Option<Self> {
-        self.0.try_move_one(self.1)
-            .map(|new_pos| Self(new_pos, self.1))
+
+    fn try_move_one_towards(&self, direction: Direction, map: &TileMap) -> Option<Self> {
+        self.0.try_move_one(direction)
+            .filter(|&pos| map.coords_are_inside(pos))
+            .map(|pos| Self(pos, direction))
     }
 
-    fn try_move_one_in(&self, map: &Map) -> Option<Self> {
-        self.try_move_one()
-            .filter(|b| map.is_point_inside(b.0))
+    fn get_all_entrances_for_map(map: &TileMap) -> impl Iterator<Item = Self> + '_ {
+        let towards_north = (0..map.width())
+            .map(|x| Beam(Coords2D(x, map.height() - 1), Direction::North));
+        let towards_east = (0..map.height())
+            .map(|y| Beam(Coords2D(0, y), Direction::East));
+        let towards_south = (0..map.width())
+            .map(|x| Beam(Coords2D(x, 0), Direction::South));
+        let towards_west = (0..map.height())
+            .map(|y| Beam(Coords2D(map.width() - 1, y), Direction::West));
+
+        towards_north.chain(towards_east).chain(towards_south).chain(towards_west)
     }
+}
 
-    fn try_move_one_towards(&self, direction: Direction) -> Option<Self> {
-        let new_beam = Self(self.0, direction);
-        new_beam.try_move_one()
+fn direction_bit(direction: Direction) -> u8 {
+    match direction {
+        Direction::North => 1,
+        Direction::East => 1 << 1,
+        Direction::South => 1 << 2,
+        Direction::West => 1 << 3,
     }
+}
 
-    fn get_all_entrances_for_map(map: &Map) -> impl Iterator<Item = Self> + '_ {
-        let towards_north = (0..map.width)
-            .map(|x| Beam(Point2D(x, map.height - 1), Direction::North));
-        let towards_east = (0..map.height)
-            .map(|y| Beam(Point2D(0, y), Direction::East));
-        let towards_south = (0..map.width)
-            .map(|x| Beam(Point2D(x, 0), Direction::South));
-        let towards_west = (0..map.height)
-            .map(|y| Beam(Point2D(map.width, y), Direction::West));
+/// Follows every beam that comes out of `from`, returning the directions beams entered each tile
+/// with as a mask of [`direction_bit`]s. A beam stops when it enters a tile with a direction
+/// another beam already did, so loops between mirrors and splitters end.
+fn trace_beam(map: &TileMap, from: Beam) -> Map2D<u8> {
+    let mut trace = Map2D::filled_with(&0, map.width(), map.height());
+    let mut beams = vec![from];
+    while let Some(beam) = beams.pop() {
+        let directions = trace.get_mut(beam.0).unwrap();
+        let bit = direction_bit(beam.1);
+        if *directions & bit != 0 {
+            continue;
+        }
+        *directions |= bit;
 
-        towards_north.chain(towards_east).chain(towards_south).chain(towards_west)
+        let tile = map.get(beam.0).unwrap();
+        beams.extend(tile.outgoing(beam.1).filter_map(|d| beam.try_move_one_towards(d, map)));
     }
+    trace
 }
 
-#[derive(Clone)]
-struct Map {
-    tiles: Vec<char>,
-    width: usize,
-    height: usize,
+fn energized_count(trace: &Map2D<u8>) -> usize {
+    trace.iter().filter(|&&directions| directions != 0).count()
 }
 
-impl Display for Map {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row_i in 0..self.height {
-            let start_i = row_i * self.width;
-            for tile in &self.tiles[start_i..(start_i + self.width)] {
-                write!(f, "{}", tile)?;
-            }
-            writeln!(f)?;
+fn directions_in(mask: u8) -> impl Iterator<Item = Direction> {
+    DIRECTIONS.into_iter().filter(move |&d| mask & direction_bit(d) != 0)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum RenderStyle {
+    /// Like the puzzle's pictures: empty tiles show the arrow of the only beam that went through
+    /// them, or how many beams did if there were more, and every other tile shows itself.
+    Arrows,
+    /// How many directions beams went through each tile with, `.` if none did.
+    Counts,
+}
+
+fn render_trace(map: &TileMap, trace: &Map2D<u8>, style: RenderStyle) -> String {
+    let mut rendered = String::with_capacity((map.width() + 1) * map.height());
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            let pos = Coords2D(x, y);
+            let tile = map.get(pos).unwrap();
+            let directions = *trace.get(pos).unwrap();
+            let passes = directions.count_ones();
+            let ch = match style {
+                RenderStyle::Arrows if *tile != Tile::Empty => tile.to_string().chars().next().unwrap(),
+                RenderStyle::Arrows if passes == 1 => match directions_in(directions).next().unwrap() {
+                    Direction::North => '^',
+                    Direction::East => '>',
+                    Direction::South => 'v',
+                    Direction::West => '<',
+                },
+                _ if passes == 0 => '.',
+                _ => char::from_digit(passes, 10).unwrap(),
+            };
+            rendered.push(ch);
         }
-        Ok(())
+        rendered.push('\n');
     }
+    rendered
 }
 
-impl Map {
-    fn display_visited(&self, visited: &HashSet<Point2D>) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let point = Point2D(x, y);
-                if visited.contains(&point) {
-                    print!("#");
-                } else {
-                    print!(".");
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct EntranceSummary {
+    entrance: Beam,
+    energized: usize,
+    /// Times a beam went through a tile, counting each direction once.
+    passes: usize,
+    /// Splitters hit on their flat side.
+    splits: usize,
+}
+
+impl EntranceSummary {
+    fn new(map: &TileMap, entrance: Beam) -> Self {
+        let trace = trace_beam(map, entrance);
+        let mut splits = 0;
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                let pos = Coords2D(x, y);
+                let tile = map.get(pos).unwrap();
+                if directions_in(*trace.get(pos).unwrap()).any(|d| tile.splits(d)) {
+                    splits += 1;
                 }
             }
-            println!();
+        }
+        Self {
+            entrance,
+            energized: energized_count(&trace),
+            passes: trace.iter().map(|directions| directions.count_ones() as usize).sum(),
+            splits,
         }
     }
-    
-    fn try_from(input: &mut impl BufRead) -> Option<Self> {
-        let mut lines = input.lines().flatten().take_while(|l| !l.is_empty());
-        let first_line = match lines.next() {
-            Some(line) => line,
-            None => return None,
-        };
-        let mut tiles: Vec<_> = first_line.chars().skip(1).collect();
-        let width = tiles.len();
-        let mut expected_length = width;
+}
+
+/// Summaries for every entrance, from the most energized tiles to the least.
+fn entrance_summaries(map: &TileMap) -> Vec<EntranceSummary> {
+    let entrances: Vec<_> = Beam::get_all_entrances_for_map(map).collect();
+    let mut summaries: Vec<_> = entrances
+        .into_par_iter()
+        .map(|beam| EntranceSummary::new(map, beam))
+        .collect();
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.energized));
+    summaries
+}
 
-        let mut height = 1;
-        for line in lines {
-            tiles.extend(line.chars());
-            height += 1;
+fn summary_table(summaries: &[EntranceSummary]) -> String {
+    let mut table = format!("{:>12} {:>9} {:>10} {:>7} {:>7}\n", "Entrance", "Direction", "Energized", "Passes", "Splits");
+    for summary in summaries {
+        let Beam(pos, direction) = summary.entrance;
+        table += &format!(
+            "{:>12} {:>9} {:>10} {:>7} {:>7}\n",
+            format!("{:?}", pos),
+            format!("{:?}", direction),
+            summary.energized,
+            summary.passes,
+            summary.splits,
+        );
+    }
+    table
+}
 
-            expected_length += width;
-            assert_eq!(tiles.len(), expected_length);
+/// Energized tile count for every entrance, computed in parallel over a shared [`SplitterGraph`].
+fn energized_counts(map: &TileMap) -> Vec<(Beam, usize)> {
+    let graph = SplitterGraph::new(map);
+    let entrances: Vec<_> = Beam::get_all_entrances_for_map(map).collect();
+    entrances
+        .into_par_iter()
+        .map(|beam| (beam, graph.energized_count(map, beam)))
+        .collect()
+}
+
+#[derive(Clone)]
+struct TileSet(Vec<u64>);
+
+impl TileSet {
+    fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn union_with(&mut self, other: &TileSet) {
+        for (word, other_word) in self.0.iter_mut().zip(&other.0) {
+            *word |= other_word;
         }
-        Some(Self {
-            tiles,
-            width,
-            height,
-        })
     }
 
-    fn get_index(&self, point: Point2D) -> Option<usize> {
-        if point.0 > self.width {
-            return None;
+    fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+/// The tiles a beam goes through until it leaves the map, loops, or hits the flat side of a splitter.
+struct BeamPath {
+    tiles: TileSet,
+    splitter: Option<usize>,
+}
+
+/// Every beam that hits the flat side of a splitter continues the same way regardless of where it
+/// came from, so the tiles energized from each splitter are computed once and shared by every
+/// entrance. Splitters that feed each other are grouped into strongly connected components, which
+/// all energize the same tiles.
+struct SplitterGraph {
+    /// Splitter number of each tile index, if it's a splitter.
+    splitters: Vec<Option<usize>>,
+    /// Component of each splitter.
+    components: Vec<usize>,
+    /// Tiles energized from each component.
+    energized: Vec<TileSet>,
+}
+
+impl SplitterGraph {
+    fn new(map: &TileMap) -> Self {
+        let mut splitters = vec![None; map.width() * map.height()];
+        let mut positions = vec![];
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                let pos = Coords2D(x, y);
+                if matches!(map.get(pos), Some(Tile::VerticalSplitter | Tile::HorizontalSplitter)) {
+                    splitters[map.get_index(pos).unwrap()] = Some(positions.len());
+                    positions.push(pos);
+                }
+            }
         }
-        let index = point.0 + (self.width * point.1);
-        if index < self.tiles.len() {
-            Some(index)
-        } else {
-            None
+
+        let mut own_tiles = Vec::with_capacity(positions.len());
+        let mut successors = Vec::with_capacity(positions.len());
+        for &pos in &positions {
+            let mut tiles = TileSet::new(splitters.len());
+            tiles.insert(map.get_index(pos).unwrap());
+            let mut next = vec![];
+            for direction in map.get(pos).unwrap().split_directions() {
+                let Some(beam) = Beam(pos, direction).try_move_one_towards(direction, map) else {
+                    continue;
+                };
+                let path = follow_path(map, &splitters, beam);
+                tiles.union_with(&path.tiles);
+                next.extend(path.splitter);
+            }
+            own_tiles.push(tiles);
+            successors.push(next);
         }
-    }
 
-    fn get(&self, point: Point2D) -> Option<&char> {
-        self.get_index(point).map(|i| self.tiles.get(i)).flatten()
+        let (components, components_len) = strongly_connected_components(&successors);
+        let mut members = vec![vec![]; components_len];
+        for (splitter, &component) in components.iter().enumerate() {
+            members[component].push(splitter);
+        }
+        // Components come out in reverse topological order, so every component a splitter leads
+        // to is already complete by the time it's needed.
+        let mut energized: Vec<TileSet> = Vec::with_capacity(components_len);
+        for component_members in &members {
+            let mut tiles = TileSet::new(splitters.len());
+            for &splitter in component_members {
+                tiles.union_with(&own_tiles[splitter]);
+                for &next in &successors[splitter] {
+                    if components[next] < energized.len() {
+                        tiles.union_with(&energized[components[next]]);
+                    }
+                }
+            }
+            energized.push(tiles);
+        }
+
+        Self {
+            splitters,
+            components,
+            energized,
+        }
     }
-    
-    fn is_point_inside(&self, point: Point2D) -> bool {
-        point.0 < self.width && point.1 < self.height
+
+    fn energized_count(&self, map: &TileMap, from: Beam) -> usize {
+        let mut path = follow_path(map, &self.splitters, from);
+        if let Some(splitter) = path.splitter {
+            path.tiles.union_with(&self.energized[self.components[splitter]]);
+        }
+        path.tiles.len()
     }
-    
-    fn get_beam_positions(&self, from: Beam) -> HashSet<Point2D> {
-        let mut visited = HashSet::default();
-        let mut splitters = HashSet::default();
-        self.get_beam_positions_cached(from, &mut visited, &mut splitters);
-        visited
+}
+
+fn follow_path(map: &TileMap, splitters: &[Option<usize>], from: Beam) -> BeamPath {
+    let mut tiles = TileSet::new(splitters.len());
+    let mut seen = HashSet::new();
+    let mut beam = from;
+    while seen.insert(beam) {
+        let index = map.get_index(beam.0).unwrap();
+        tiles.insert(index);
+
+        let tile = map.get(beam.0).unwrap();
+        if tile.splits(beam.1) {
+            return BeamPath { tiles, splitter: splitters[index] };
+        }
+        match beam.try_move_one_towards(tile.reflect(beam.1), map) {
+            Some(next) => beam = next,
+            None => break,
+        }
     }
+    BeamPath { tiles, splitter: None }
+}
 
-    fn get_beam_positions_cached(&self, from: Beam, visited: &mut HashSet<Point2D>,
-                                 splitters: &mut HashSet<Point2D>,
-    ) {
-        let mut cur_beam = from;
-        loop {
-            if !self.is_point_inside(cur_beam.0) {
-                break;
+/// Tarjan's algorithm without recursion. Returns the component of each node, numbered in reverse
+/// topological order, and the amount of components.
+fn strongly_connected_components(successors: &[Vec<usize>]) -> (Vec<usize>, usize) {
+    const UNVISITED: usize = usize::MAX;
+    let len = successors.len();
+    let mut index = vec![UNVISITED; len];
+    let mut low_link = vec![0; len];
+    let mut on_stack = vec![false; len];
+    let mut stack = vec![];
+    let mut components = vec![0; len];
+    let mut components_len = 0;
+    let mut next_index = 0;
+
+    for root in 0..len {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        let mut calls = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(node, successor_i)) = calls.last() {
+            if let Some(&next) = successors[node].get(successor_i) {
+                calls.last_mut().unwrap().1 += 1;
+                if index[next] == UNVISITED {
+                    index[next] = next_index;
+                    low_link[next] = next_index;
+                    next_index += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    calls.push((next, 0));
+                } else if on_stack[next] {
+                    low_link[node] = low_link[node].min(index[next]);
+                }
+                continue;
             }
-            
-            visited.insert(cur_beam.0);
-            
-            let tile = match self.get(cur_beam.0) {
-                Some(tile) => tile,
-                None => break,
-            };
-            match tile {
-                '|' => match cur_beam.1 {
-                    Direction::West | Direction::East => {
-                        self.split_beam(cur_beam, visited, splitters);
-                        break;
-                    },
-                    _ => {}
-                },
-                '-' => match cur_beam.1 {
-                    Direction::North | Direction::South => {
-                        self.split_beam(cur_beam, visited, splitters);
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+            if low_link[node] == index[node] {
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    components[member] = components_len;
+                    if member == node {
                         break;
-                    },
-                    _ => {}
-                },
-                '/' => {
-                    let towards = match cur_beam.1 {
-                        Direction::North | Direction::South => cur_beam.1.turn_right(),
-                        Direction::East | Direction::West => cur_beam.1.turn_left(),
-                    };
-                    if let Some(new_beam) = cur_beam.try_move_one_towards(towards) {
-                        // println!("{:?} hit a / mirror, now it's {:?}", cur_beam, new_beam);
-                        self.get_beam_positions_cached(new_beam, visited, splitters);
                     }
-                    break;
-                },
-                '\\' => {
-                    let towards = match cur_beam.1 {
-                        Direction::North | Direction::South => cur_beam.1.turn_left(),
-                        Direction::East | Direction::West => cur_beam.1.turn_right(),
-                    };
-                    if let Some(new_beam) = cur_beam.try_move_one_towards(towards) {
-                        // println!("{:?} hit a \\ mirror, now it's {:?}", cur_beam, new_beam);
-                        self.get_beam_positions_cached(new_beam, visited, splitters);
-                    }
-                    break;
-                },
-                '.' => {
-                    
-                },
-                _ => panic!("Invalid tile: {}", tile),
+                }
+                components_len += 1;
             }
-            
-            cur_beam = match cur_beam.try_move_one() {
-                Some(beam) => beam,
-                None => break,
-            };
         }
     }
+    (components, components_len)
+}
 
-    fn split_beam(&self, from: Beam, visited: &mut HashSet<Point2D>,
-                                 splitters: &mut HashSet<Point2D>,
-    ) {
-        if splitters.contains(&from.0) {
-            // println!("Stopped splitters loop at {from:?}");
-            return;
-        }
-        splitters.insert(from.0);
-        
-        if let Some(left_beam) = from.try_move_one_towards(from.1.turn_left()) {
-            self.get_beam_positions_cached(left_beam, visited, splitters);
-        }
+#[cfg(test)]
+mod tests {
+    use aoc_shared::coords2d::Coords2D;
+    use aoc_shared::direction::Direction;
+    use aoc_shared::rng::{random_grid, random_grids, Lcg};
+    use crate::{Beam, energized_count, energized_counts, entrance_summaries, render_trace, RenderStyle, summary_table, TileMap, trace_beam};
+
+    const EXAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
+
+    fn parse_map(s: &str) -> TileMap {
+        TileMap::try_from_reader(&mut s.as_bytes()).unwrap().unwrap()
+    }
+
+    fn tile(rng: &mut Lcg) -> char {
+        ['.', '.', '.', '.', '.', '/', '\\', '|', '-'][rng.below(9) as usize]
+    }
+
+    #[test]
+    fn energizes_example_from_top_left() {
+        let map = parse_map(EXAMPLE);
+
+        let trace = trace_beam(&map, Beam::starting());
+
+        assert_eq!(energized_count(&trace), 46);
+    }
+
+    #[test]
+    fn finds_best_entrance_of_example() {
+        let map = parse_map(EXAMPLE);
+
+        let best = energized_counts(&map).into_iter().max_by_key(|&(_, count)| count);
+
+        assert_eq!(best, Some((Beam(Coords2D(3, 0), Direction::South), 51)));
+    }
+
+    #[test]
+    fn west_entrances_start_inside_the_map() {
+        let map = parse_map("...\n...");
+
+        let entrances: Vec<_> = Beam::get_all_entrances_for_map(&map)
+            .filter(|b| b.1 == Direction::West)
+            .collect();
+
+        assert_eq!(entrances, vec![Beam(Coords2D(2, 0), Direction::West), Beam(Coords2D(2, 1), Direction::West)]);
+    }
+
+    #[test]
+    fn stops_on_mirror_loops() {
+        let map = parse_map("/.\\\n...\n\\./");
+
+        let trace = trace_beam(&map, Beam(Coords2D(1, 0), Direction::East));
+
+        assert_eq!(energized_count(&trace), 8);
+    }
+
+    #[test]
+    fn shared_splitter_graph_matches_tracing_each_entrance() {
+        for ((width, height), input) in random_grids(&[(1, 1), (5, 3), (20, 20), (60, 45)], tile) {
+            let map = parse_map(&input);
 
-        if let Some(right_beam) = from.try_move_one_towards(from.1.turn_right()) {
-            self.get_beam_positions_cached(right_beam, visited, splitters);
+            for (beam, count) in energized_counts(&map) {
+                let expected = energized_count(&trace_beam(&map, beam));
+                assert_eq!(count, expected, "{}x{} from {:?}", width, height, beam);
+            }
         }
     }
+
+    #[test]
+    fn traces_large_map_without_overflowing_the_stack() {
+        let map = parse_map(&random_grid(400, 400, 7, tile));
+
+        let trace = trace_beam(&map, Beam::starting());
+
+        assert!(energized_count(&trace) > 0);
+    }
+
+    #[test]
+    fn renders_arrows_like_the_example() {
+        let map = parse_map(EXAMPLE);
+        let trace = trace_beam(&map, Beam::starting());
+
+        let rendered = render_trace(&map, &trace, RenderStyle::Arrows);
+
+        let expected = r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn renders_direction_counts() {
+        let map = parse_map("...\n.|.");
+        let trace = trace_beam(&map, Beam(Coords2D(0, 1), Direction::East));
+
+        let rendered = render_trace(&map, &trace, RenderStyle::Counts);
+
+        assert_eq!(rendered, ".1.\n11.\n");
+    }
+
+    #[test]
+    fn summarises_entrances_from_best_to_worst() {
+        let map = parse_map(EXAMPLE);
+
+        let summaries = entrance_summaries(&map);
+        let table = summary_table(&summaries);
+
+        assert_eq!(summaries.len(), 40);
+        assert_eq!(summaries[0].entrance, Beam(Coords2D(3, 0), Direction::South));
+        assert_eq!(summaries[0].energized, 51);
+        assert!(summaries.windows(2).all(|w| w[0].energized >= w[1].energized));
+        assert_eq!(table.lines().count(), 41);
+        assert!(table.lines().nth(1).unwrap().contains("South"));
+    }
 }
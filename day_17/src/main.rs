@@ -1,20 +1,12 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap, HashSet};
-use std::fmt::{Debug, Display, Formatter};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::hash::Hash;
 use std::io::BufReader;
 use std::time::{Duration, Instant};
 use aoc_shared::coords2d::Coords2D;
-use aoc_shared::direction::{Direction, DIRECTIONS};
+use aoc_shared::direction::Direction;
 use aoc_shared::map2d::Map2D;
-use sorted_vec::SortedVec;
-use aoc_shared::vector2d::Vector2D;
-
-const MOVE_COST_PENALTY: usize = 10;
-const HEURISTIC_PENALTY: usize = 1;
-const MIN_STRAIGHT_LINE: usize = 4; // Min is 1
-const MAX_STRAIGHT_LINE: usize = 10;
 
 fn main() {
     let path = std::env::current_dir().unwrap().join("day_17/input.txt");
@@ -25,30 +17,68 @@ fn main() {
     
     let map = TileMap::try_from_reader(&mut reader).unwrap().unwrap();
     // println!("{}", map);
-    
-    part_1(&map);
+
+    let options = Options::from_args(std::env::args().skip(1), &map);
+    for part in options.parts {
+        let rules = match part {
+            1 => CrucibleRules::CRUCIBLE,
+            2 => CrucibleRules::ULTRA_CRUCIBLE,
+            _ => panic!("There's no part {}", part),
+        };
+        println!("Part {} ({:?}):", part, rules);
+        solve(&map, rules, options.source, options.target);
+        println!();
+    }
 }
 
-fn part_1(map: &TileMap) {
-    let start = Coords2D::ZERO;
-    let destination = Coords2D(map.width() - 1, map.height() - 1);
-    let path = pathfind_map(&map, start, destination).expect("No path found!");
-    // println!("{path:?}");
+struct Options {
+    parts: Vec<usize>,
+    source: Coords2D,
+    target: Coords2D,
+}
 
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>, map: &TileMap) -> Self {
+        let mut options = Self {
+            parts: vec![1, 2],
+            source: Coords2D::ZERO,
+            target: Coords2D(map.width() - 1, map.height() - 1),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().unwrap_or_else(|| panic!("{} expects a value", arg));
+            match arg.as_str() {
+                "--part" => options.parts = vec![value().parse().expect("Expected a part number")],
+                "--from" => options.source = parse_coords(&value()),
+                "--to" => options.target = parse_coords(&value()),
+                _ => panic!("Unexpected argument: {}", arg),
+            }
+        }
+        options
+    }
+}
+
+/// Parses coordinates written as `x,y`.
+fn parse_coords(s: &str) -> Coords2D {
+    let (x, y) = s.split_once(',').expect("Expected coordinates as x,y");
+    Coords2D(x.trim().parse().unwrap(), y.trim().parse().unwrap())
+}
+
+fn solve(map: &TileMap, rules: CrucibleRules, source: Coords2D, target: Coords2D) {
+    let (result, stats) = find_path(map, rules, source, target);
+    println!("{}", stats);
+    let Some(result) = result else {
+        println!("No path found from {:?} to {:?}!", source, target);
+        return;
+    };
+
+    display_path(map, &result.path);
     println!();
-    display_path(map, &path);
-    println!();
-    
-    println!("Path length: {}", path.len());
-    let heat_loss: usize = path
-        .iter()
-        .map(|&pos| map.get(pos).unwrap().cost)
-        .sum();
-    println!("Heat loss: {}", heat_loss);
 
+    println!("Path length: {}", result.path.len());
+    println!("Heat loss: {}", result.heat_loss);
 }
 
-fn display_path(map: &TileMap, path: &Vec<Coords2D>) {
+fn display_path(map: &TileMap, path: &[Coords2D]) {
     let mask = HashSet::<Coords2D>::from_iter(path.iter().cloned());
     for y in 0..map.height() {
         for x in 0..map.width() {
@@ -63,243 +93,197 @@ fn display_path(map: &TileMap, path: &Vec<Coords2D>) {
     }
 }
 
-fn pathfind_map(map: &TileMap, start: Coords2D, destination: Coords2D) -> Option<Vec<Coords2D>> {
-    println!("Travelling from {:?} to {:?}. Distance: {}", start, destination,
-             start.manhattan_distance_to(destination));
-
-    let start_instant = Instant::now();
-    let mut open_list = BinaryHeap::<ReverseCostOrder>::default();
-    let mut closed_list = HashMap::<Movement, Breadcrumb>::default();
-
-    let mut insert_instant = Instant::now();
-    open_list.extend(DIRECTIONS
-        .iter()
-        .flat_map(|&d| get_next_points_for_direction(map, start, destination, d))
-        .map(ReverseCostOrder)
-    );
-    let mut open_list_insert_duration = insert_instant.elapsed();
-    let mut open_list_insert_amount = open_list.len() as u32;
-    let mut open_list_pop_duration = Duration::ZERO;
-    let mut open_list_pop_amount = 0;
-    // println!("{:?}", open_list.iter().rev().collect::<Vec<_>>());
-    
-    let mut best_candidate: Option<Breadcrumb> = None;
-    
-
-    let mut i = 0;
-    'outer_while: while open_list.len() > 0 {
-        let pop_instant = Instant::now();
-        let breadcrumb = open_list.pop().unwrap().0;
-        open_list_pop_duration += pop_instant.elapsed();
-        open_list_pop_amount += 1;
-
-        if closed_list.contains_key(&breadcrumb.movement) {
-            continue;
-        }
-        closed_list.insert(breadcrumb.movement, breadcrumb.clone());
+/// How many blocks a crucible has to move in a straight line before it can turn or stop, and
+/// how many it can move before it has to turn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct CrucibleRules {
+    min_run: usize,
+    max_run: usize,
+}
 
-        for new_breadcrumb in get_next_points_from_breadcrumb(map, &breadcrumb, destination) {
-            if closed_list.contains_key(&new_breadcrumb.movement) {
-                continue;
-            }
-            
-            if new_breadcrumb.heuristic_cost != 0 {
-                let time = Instant::now();
-                open_list.push(ReverseCostOrder(new_breadcrumb));
-                open_list_insert_duration += time.elapsed();
-                open_list_insert_amount += 1;
-                continue;
-            }
+impl CrucibleRules {
+    const CRUCIBLE: Self = Self { min_run: 1, max_run: 3 };
+    const ULTRA_CRUCIBLE: Self = Self { min_run: 4, max_run: 10 };
+}
 
-            if best_candidate.as_ref().is_some_and(|c| c.move_cost <= new_breadcrumb.move_cost) {
-                continue;
-            }
-            
-            println!("Reached candidate in {} iteration/s!", i + 1);
-            println!("Candidate has cost: {}", new_breadcrumb.move_cost / MOVE_COST_PENALTY);
-            closed_list.insert(new_breadcrumb.movement, new_breadcrumb.clone());
-            best_candidate = Some(new_breadcrumb.clone());
-        }
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
 
-        closed_list.insert(breadcrumb.movement, breadcrumb.clone());
-        i += 1;
-
-        if (i % 10000) == 0 {
-            println!("Iteration {i}:");
-            println!("- Open list len: {}", open_list.len());
-            println!("- Insert time average: {:?}", open_list_insert_duration / open_list_insert_amount);
-            println!("- Insert time total: {:?}", open_list_insert_duration);
-            println!("- Pop time average: {:?}", open_list_pop_duration / open_list_pop_amount);
-            println!("- Pop time total: {:?}", open_list_pop_duration);
-            println!("- Total time: {:?}", start_instant.elapsed());
-            println!("- Closed list len: {}", closed_list.len());
-            println!("- Closest node: {:?}", closed_list.values().map(|x| x.heuristic_cost).min());
-            println!("- Cheapest next node: {:?}", open_list.iter().map(|x| x.0.final_cost).min());
-            println!("- Cheapest cost move in open list: {:?}", open_list.iter().map(|x| x.0.move_cost).min().and_then(|x| Some(x / MOVE_COST_PENALTY)));
+impl Axis {
+    fn directions(&self) -> [Direction; 2] {
+        match self {
+            Self::Horizontal => [Direction::East, Direction::West],
+            Self::Vertical => [Direction::North, Direction::South],
         }
     }
-    
-    return best_candidate.map(|b| create_path(&b, closed_list));
-    
-    fn create_path(
-        final_breadcrumb: &Breadcrumb,
-        breadcrumbs: HashMap<Movement, Breadcrumb>,
-    ) -> Vec<Coords2D> {
-        let mut path = vec![];
-        let mut cur_breadcrumb = final_breadcrumb;
-        loop {
-            let mut position = cur_breadcrumb.position();
-            let reverse = Vector2D::from(cur_breadcrumb.direction.reverse());
-            path.push(position);
-            for _ in 1..cur_breadcrumb.moved_amount {
-                position = position.try_move(reverse).unwrap();
-                path.push(position);
-            }
-            
-            cur_breadcrumb = match cur_breadcrumb.parent {
-                Some(movement) => breadcrumbs.get(&movement).unwrap(),
-                None => break,
-            };
+
+    fn perpendicular(&self) -> Self {
+        match self {
+            Self::Horizontal => Self::Vertical,
+            Self::Vertical => Self::Horizontal,
         }
-        path.reverse();
-        path
     }
 }
 
-fn get_next_points_from_breadcrumb<'a>(
-    map: &'a TileMap,
-    from: &'a Breadcrumb,
-    destination: Coords2D,
-) -> impl Iterator<Item = Breadcrumb> + 'a {
-    get_next_points_for_direction(map, from.position(), destination, from.direction.turn_left())
-        .chain(get_next_points_for_direction(map, from.position(), destination, from.direction.turn_right()))
-        .map(|b| b.with_previous(from))
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct CruciblePath {
+    heat_loss: usize,
+    /// Every block the crucible goes through, including the source.
+    path: Vec<Coords2D>,
 }
 
-fn get_next_points_for_direction<'a>(
-    map: &'a TileMap,
-    from: Coords2D,
-    destination: Coords2D,
-    direction: Direction,
-) -> impl Iterator<Item = Breadcrumb> + 'a {
-    let mut current: Option<Coords2D> = Some(from);
-    let mut moved_amount = MIN_STRAIGHT_LINE - 1;
-    let mut move_cost = 0;
-    // println!("{:?} -> {:?} {:?}", from, direction, MIN_STRAIGHT_LINE..MAX_STRAIGHT_LINE);
-    for _ in 0..moved_amount {
-        let next_pos = match current.and_then(|c| c.try_move_one(direction)) {
-            Some(coords) => coords,
-            None => {
-                current = None;
-                break;
-            },
-        };
-
-        if let Some(tile) = map.get(next_pos) {
-            current = Some(next_pos);
-            move_cost += tile.cost * MOVE_COST_PENALTY;
-            // println!("Precalculating {:?} cost to be {}", next_pos, move_cost);
-        } else {
-            current = None;
-            break;
-        }
-    }
-    
-    std::iter::from_fn(move || {
-        if moved_amount >= MAX_STRAIGHT_LINE {
-            return None;
-        }
+/// A block the crucible stopped at, and the axis it moved along to get there. Every run ends with
+/// a turn, so that's all that's needed to know where it can go next.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct State(Coords2D, Axis);
 
-        let next_pos = match current.and_then(|c| c.try_move_one(direction)) {
-            Some(coords) => coords,
-            None => return None,
+impl State {
+    fn index(&self, map: &TileMap) -> usize {
+        let axis = match self.1 {
+            Axis::Horizontal => 0,
+            Axis::Vertical => 1,
         };
+        map.get_index(self.0).unwrap() * 2 + axis
+    }
 
-        if let Some(tile) = map.get(next_pos) {
-            current = Some(next_pos);
-            move_cost += tile.cost * MOVE_COST_PENALTY;
-            moved_amount += 1;
-            // println!("Calculated {:?} cost is {} and moved {} times", next_pos, move_cost, moved_amount);
-            let heuristic_cost = next_pos.manhattan_distance_to(destination);
-            let cur_movement = Breadcrumb {
-                movement: Movement(from, next_pos),
-                move_cost,
-                heuristic_cost,
-                moved_amount,
-                direction,
-                final_cost: move_cost + heuristic_cost,
-                parent: None
-            };
-            Some(cur_movement)
-        } else {
-            None
-        }
-    })
+    fn from_index(index: usize, map: &TileMap) -> Self {
+        let tile_index = index / 2;
+        let axis = if index.is_multiple_of(2) { Axis::Horizontal } else { Axis::Vertical };
+        Self(Coords2D(tile_index % map.width(), tile_index / map.width()), axis)
+    }
 }
 
-#[derive(Debug, Clone)]
-struct Breadcrumb {
-    movement: Movement,
-    move_cost: usize,
-    heuristic_cost: usize,
-    final_cost: usize,
-    moved_amount: usize,
-    direction: Direction,
-    parent: Option<Movement>,
+/// Counters of how much work a search did, for the caller to report.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct SearchStats {
+    /// States taken out of the open list, including outdated entries.
+    pops: usize,
+    /// States put in the open list.
+    pushes: usize,
+    /// Entries skipped because a cheaper way to their state was found after they were pushed.
+    stale_pops: usize,
+    max_open_list_len: usize,
+    elapsed: Duration,
 }
 
-impl Breadcrumb {
-    fn position(&self) -> Coords2D {
-        self.movement.1
-    }
-    
-    fn with_previous(&self, previous: &Breadcrumb) -> Self {
-        let extra_cost = previous.move_cost;
-        Self {
-            move_cost: self.move_cost + extra_cost,
-            final_cost: self.final_cost + extra_cost,
-            parent: Some(previous.movement),
-            ..self.clone()
-        }
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "- Pops: {} ({} stale)", self.pops, self.stale_pops)?;
+        writeln!(f, "- Pushes: {}", self.pushes)?;
+        writeln!(f, "- Max open list len: {}", self.max_open_list_len)?;
+        write!(f, "- Total time: {:?}", self.elapsed)
     }
 }
 
-#[derive(Debug)]
-struct ReverseCostOrder(pub Breadcrumb);
-
-impl Eq for ReverseCostOrder {}
-
-impl PartialEq<Self> for ReverseCostOrder {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.final_cost.eq(&other.0.final_cost)
-    }
+/// Finds the path from `source` to `target` that loses the least heat with A*, taking every run
+/// the rules allow as a single edge.
+///
+/// Every block costs at least as much as the cheapest block in the map, so that times the
+/// manhattan distance never overestimates the remaining heat loss, and the first time the target
+/// comes out of the open list its path is the optimal one.
+fn find_path(map: &TileMap, rules: CrucibleRules, source: Coords2D, target: Coords2D) -> (Option<CruciblePath>, SearchStats) {
+    let min_cost = map.iter().map(|tile| tile.cost).min().unwrap_or(0);
+    search(map, rules, source, target, |position| min_cost * position.manhattan_distance_to(target))
 }
 
-impl PartialOrd<Self> for ReverseCostOrder {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
+fn search(
+    map: &TileMap,
+    rules: CrucibleRules,
+    source: Coords2D,
+    target: Coords2D,
+    heuristic: impl Fn(Coords2D) -> usize,
+) -> (Option<CruciblePath>, SearchStats) {
+    let start_instant = Instant::now();
+    let mut stats = SearchStats::default();
+    if !map.coords_are_inside(source) || !map.coords_are_inside(target) {
+        return (None, stats);
+    }
+    if source == target {
+        stats.elapsed = start_instant.elapsed();
+        return (Some(CruciblePath { heat_loss: 0, path: vec![source] }), stats);
     }
-}
 
-impl Ord for ReverseCostOrder {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.0.final_cost.cmp(&other.0.final_cost).reverse()
+    let states_len = map.width() * map.height() * 2;
+    let mut heat_losses = vec![usize::MAX; states_len];
+    let mut parents: Vec<Option<State>> = vec![None; states_len];
+    // Ordered by the estimated total heat loss, then by the heat loss so far
+    let mut open_list = BinaryHeap::new();
+
+    // The crucible can start moving along any axis, as if it had just turned from the other one
+    for axis in [Axis::Horizontal, Axis::Vertical] {
+        let state = State(source, axis);
+        heat_losses[state.index(map)] = 0;
+        open_list.push(Reverse((heuristic(source), 0, state.index(map))));
+        stats.pushes += 1;
     }
-}
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
-struct Movement(Coords2D, Coords2D);
+    let mut result = None;
+    while let Some(Reverse((_, heat_loss, index))) = open_list.pop() {
+        stats.pops += 1;
+        if heat_loss > heat_losses[index] {
+            stats.stale_pops += 1;
+            continue;
+        }
+        let state = State::from_index(index, map);
+        if state.0 == target {
+            result = Some(CruciblePath { heat_loss, path: create_path(map, state, &parents) });
+            break;
+        }
 
-impl Movement {
-    fn heuristic_cost(&self, destination: Coords2D) -> usize {
-        self.1.manhattan_distance_to(destination) * HEURISTIC_PENALTY
+        let axis = state.1.perpendicular();
+        for direction in axis.directions() {
+            let mut position = state.0;
+            let mut run_heat_loss = heat_loss;
+            for run in 1..=rules.max_run {
+                let Some(next) = position.try_move_one(direction).filter(|&p| map.coords_are_inside(p)) else {
+                    break;
+                };
+                position = next;
+                run_heat_loss += map.get(position).unwrap().cost;
+                if run < rules.min_run {
+                    continue;
+                }
+
+                let next_state = State(position, axis);
+                let next_index = next_state.index(map);
+                if run_heat_loss < heat_losses[next_index] {
+                    heat_losses[next_index] = run_heat_loss;
+                    parents[next_index] = Some(state);
+                    open_list.push(Reverse((run_heat_loss + heuristic(position), run_heat_loss, next_index)));
+                    stats.pushes += 1;
+                }
+            }
+        }
+        stats.max_open_list_len = stats.max_open_list_len.max(open_list.len());
     }
+    stats.elapsed = start_instant.elapsed();
+    (result, stats)
 }
 
-impl Debug for Movement {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({:?} -> {:?})", self.0, self.1)
+fn create_path(map: &TileMap, last: State, parents: &[Option<State>]) -> Vec<Coords2D> {
+    let mut path = vec![];
+    let mut current = last;
+    while let Some(parent) = parents[current.index(map)] {
+        // Fill in every block of the run, walking back towards the parent
+        let mut position = current.0;
+        while position != parent.0 {
+            path.push(position);
+            position = step_towards(position, parent.0);
+        }
+        current = parent;
     }
+    path.push(current.0);
+    path.reverse();
+    path
+}
+
+fn step_towards(from: Coords2D, to: Coords2D) -> Coords2D {
+    let x = if from.0 < to.0 { from.0 + 1 } else if from.0 > to.0 { from.0 - 1 } else { from.0 };
+    let y = if from.1 < to.1 { from.1 + 1 } else if from.1 > to.1 { from.1 - 1 } else { from.1 };
+    Coords2D(x, y)
 }
 
 #[derive(Debug)]
@@ -324,3 +308,109 @@ impl TryFrom<char> for Tile {
 }
 
 type TileMap = Map2D<Tile>;
+
+#[cfg(test)]
+mod tests {
+    use aoc_shared::coords2d::Coords2D;
+    use aoc_shared::rng::{random_grids, Lcg};
+    use crate::{CrucibleRules, find_path, search, TileMap};
+
+    const EXAMPLE: &str = "\
+2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+
+    fn parse_map(s: &str) -> TileMap {
+        TileMap::try_from_reader(&mut s.as_bytes()).unwrap().unwrap()
+    }
+
+    fn corner(map: &TileMap) -> Coords2D {
+        Coords2D(map.width() - 1, map.height() - 1)
+    }
+
+    #[test]
+    fn finds_least_heat_loss_for_crucible() {
+        let map = parse_map(EXAMPLE);
+
+        let result = find_path(&map, CrucibleRules::CRUCIBLE, Coords2D::ZERO, corner(&map)).0.unwrap();
+
+        assert_eq!(result.heat_loss, 102);
+    }
+
+    #[test]
+    fn finds_least_heat_loss_for_ultra_crucible() {
+        let map = parse_map(EXAMPLE);
+        let other_map = parse_map("111111111111\n999999999991\n999999999991\n999999999991\n999999999991");
+
+        let result = find_path(&map, CrucibleRules::ULTRA_CRUCIBLE, Coords2D::ZERO, corner(&map)).0.unwrap();
+        let other_result = find_path(&other_map, CrucibleRules::ULTRA_CRUCIBLE, Coords2D::ZERO, corner(&other_map)).0.unwrap();
+
+        assert_eq!(result.heat_loss, 94);
+        assert_eq!(other_result.heat_loss, 71);
+    }
+
+    #[test]
+    fn path_heat_loss_matches_tiles() {
+        let map = parse_map(EXAMPLE);
+
+        let result = find_path(&map, CrucibleRules::ULTRA_CRUCIBLE, Coords2D::ZERO, corner(&map)).0.unwrap();
+
+        let heat_loss: usize = result.path[1..].iter().map(|&p| map.get(p).unwrap().cost).sum();
+        assert_eq!(result.path.first(), Some(&Coords2D::ZERO));
+        assert_eq!(result.path.last(), Some(&corner(&map)));
+        assert!(result.path.windows(2).all(|w| w[0].manhattan_distance_to(w[1]) == 1));
+        assert_eq!(heat_loss, result.heat_loss);
+    }
+
+    #[test]
+    fn finds_path_between_any_pair() {
+        let map = parse_map(EXAMPLE);
+
+        let result = find_path(&map, CrucibleRules::CRUCIBLE, Coords2D(2, 0), Coords2D(0, 1)).0.unwrap();
+        let same = find_path(&map, CrucibleRules::CRUCIBLE, Coords2D(2, 0), Coords2D(2, 0)).0.unwrap();
+
+        // (2, 0) -> (2, 1) -> (1, 1) -> (0, 1)
+        assert_eq!(result.heat_loss, 1 + 2 + 3);
+        assert_eq!(result.path, vec![Coords2D(2, 0), Coords2D(2, 1), Coords2D(1, 1), Coords2D(0, 1)]);
+        assert_eq!(same.heat_loss, 0);
+    }
+
+    #[test]
+    fn fails_when_runs_cannot_reach_target() {
+        let map = parse_map("123\n456");
+
+        let (result, _) = find_path(&map, CrucibleRules::ULTRA_CRUCIBLE, Coords2D::ZERO, Coords2D(2, 1));
+
+        assert!(result.is_none());
+    }
+
+    fn tile(rng: &mut Lcg) -> char {
+        char::from_digit(1 + rng.below(9) as u32, 10).unwrap()
+    }
+
+    #[test]
+    fn heuristic_keeps_optimal_heat_loss_with_less_work() {
+        for ((width, height), input) in random_grids(&[(5, 5), (13, 13), (30, 20), (50, 50)], tile) {
+            let map = parse_map(&input);
+            let target = corner(&map);
+
+            for rules in [CrucibleRules::CRUCIBLE, CrucibleRules::ULTRA_CRUCIBLE] {
+                let (a_star, a_star_stats) = find_path(&map, rules, Coords2D::ZERO, target);
+                let (dijkstra, dijkstra_stats) = search(&map, rules, Coords2D::ZERO, target, |_| 0);
+
+                assert_eq!(a_star.map(|p| p.heat_loss), dijkstra.map(|p| p.heat_loss), "{}x{} {:?}", width, height, rules);
+                assert!(a_star_stats.pops <= dijkstra_stats.pops);
+            }
+        }
+    }
+}
@@ -1,17 +1,31 @@
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::ops::Add;
 use std::str::FromStr;
-use regex_macro::{regex, Regex};
+use regex_macro::regex;
 use aoc_shared::direction::Direction;
-use aoc_shared::vector2d::Vector2D;
+use aoc_shared::vector2d::{Segment2D, Vector2D};
 
-const REGEX_TEXT: &'static str = r"(\w+) (\d+) \(#([\d\w]{6})";
+const REGEX_TEXT: &str = r"^(\w+) (\d+) \(#([0-9a-fA-F]{6})\)$";
 
 fn main() {
-    // part_1();
-    part_2();
+    let plan = DigPlan::from_reader(read_file()).unwrap_or_else(|err| panic!("{}", err));
+    for encoding in [Encoding::Plain, Encoding::Hex] {
+        for issue in plan.validate(encoding) {
+            println!("{:?} plan: {}", encoding, issue);
+        }
+    }
+
+    println!("Part 1: {}", plan.lagoon_area(Encoding::Plain));
+    println!("Part 2: {}", plan.lagoon_area(Encoding::Hex));
+
+    if std::env::args().any(|arg| arg == "--render") {
+        match plan.render(Encoding::Plain) {
+            Some(rendered) => print!("{}", rendered),
+            None => println!("The lagoon is too big to render"),
+        }
+    }
 }
 
 fn read_file() -> impl BufRead {
@@ -21,131 +35,478 @@ fn read_file() -> impl BufRead {
     BufReader::new(file)
 }
 
-fn part_1() {
-    let path: Vec<DigInstruction> = read_file().lines()
-        .map(|l| l.unwrap().parse::<DigInstruction>().unwrap())
-        .collect();
+/// Corners of the trench, starting from the origin.
+fn create_polygon<'a>(path: impl Iterator<Item = &'a DigInstruction>) -> Vec<(i128, i128)> {
+    let mut polygon = vec![];
+    let mut cur_pos = (0, 0);
+    for instruction in path {
+        polygon.push(cur_pos);
+        let (x, y) = instruction.dig_vector();
+        cur_pos = (cur_pos.0 + x, cur_pos.1 + y);
+    }
+    polygon
+}
 
-    // println!("{:#?}", path);
+fn get_interior_area(points: &[(i128, i128)]) -> i128 {
+    let n = points.len();
+    let mut area = 0;
 
-    let edges_points = path.iter().map(|x| x.amount).sum::<usize>();
+    for i in 0..n {
+        let j = (i + 1) % n;
+        area += points[i].0 * points[j].1;
+        area -= points[j].0 * points[i].1;
+    }
 
-    let polygon = create_polygon(path.into_iter());
+    area.abs() / 2
+}
+
+/// Area of the lagoon dug by following the instructions, counting the trench itself.
+///
+/// The shoelace formula gives the area enclosed by the center of the trench, and Pick's theorem
+/// adds the half of the trench outside of it.
+fn lagoon_area<'a>(path: impl Iterator<Item = &'a DigInstruction> + Clone) -> i128 {
+    let edges_points: i128 = path.clone().map(|x| x.amount as i128).sum();
+
+    let polygon = create_polygon(path);
     let interior_points = get_interior_area(&polygon);
 
-    let total_area = interior_points + (edges_points / 2) + 1;
-    println!("{}", total_area);
+    interior_points + (edges_points / 2) + 1
 }
 
-fn part_2() {
-    let path = read_file().lines()
-        .map(|l| DigInstruction::from_hex_code(&l.unwrap()))
-        .collect::<Vec<_>>();
+/// Something that makes the lagoon area meaningless, with instructions numbered from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PlanIssue {
+    /// The trench ends somewhere other than the origin.
+    NotClosed { end: Vector2D },
+    /// The instruction digs back over the one right before it.
+    Backtracks { instruction: usize },
+    /// Two instructions that don't follow each other cross or touch.
+    SelfIntersects { first: usize, second: usize },
+    /// Two instructions that don't follow each other dig along the same stretch of trench.
+    Overlaps { first: usize, second: usize },
+}
 
-    // println!("{:#?}", path);
+impl Display for PlanIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotClosed { end } => write!(f, "the trench ends at {:?} instead of the origin", end),
+            Self::Backtracks { instruction } => write!(f, "instruction {} digs back over the previous one", instruction),
+            Self::SelfIntersects { first, second } => write!(f, "instructions {} and {} cross each other", first, second),
+            Self::Overlaps { first, second } => write!(f, "instructions {} and {} dig along the same tiles", first, second),
+        }
+    }
+}
 
-    let edges_points = path.iter().map(|x| x.amount).sum::<usize>();
+/// Checks that the instructions dig a single loop back to the origin, which is what the area
+/// calculation relies on.
+fn validate<'a>(path: impl Iterator<Item = &'a DigInstruction>) -> Vec<PlanIssue> {
+    let instructions: Vec<_> = path.collect();
+    let mut segments = Vec::with_capacity(instructions.len());
+    let mut cur_pos = Vector2D::ZERO;
+    for instruction in &instructions {
+        let next_pos = cur_pos + Vector2D::from(instruction.direction) * instruction.amount;
+        segments.push(Segment2D(cur_pos, next_pos));
+        cur_pos = next_pos;
+    }
 
-    let polygon = create_polygon(path.into_iter());
-    let interior_points = get_interior_area(&polygon);
+    let mut issues = vec![];
+    let closed = cur_pos == Vector2D::ZERO;
+    if !closed {
+        issues.push(PlanIssue::NotClosed { end: cur_pos });
+    }
 
-    let total_area = interior_points + (edges_points / 2) + 1;
-    println!("{}", total_area);
+    let len = segments.len();
+    let follows = |first: usize, second: usize| {
+        second == first + 1 || (closed && first == 0 && second == len - 1)
+    };
+    for second in 0..len {
+        let previous = if second == 0 && closed { len.checked_sub(1) } else { second.checked_sub(1) };
+        if let Some(previous) = previous.filter(|&p| p != second) {
+            if instructions[second].direction == instructions[previous].direction.reverse() {
+                issues.push(PlanIssue::Backtracks { instruction: second });
+            }
+        }
+        for first in 0..second {
+            if follows(first, second) {
+                continue;
+            }
+            if segments[first].overlaps(&segments[second]) {
+                issues.push(PlanIssue::Overlaps { first, second });
+            } else if segments[first].intersects(&segments[second]) {
+                issues.push(PlanIssue::SelfIntersects { first, second });
+            }
+        }
+    }
+    issues
 }
 
-fn create_polygon(path: impl Iterator<Item = DigInstruction>) -> Vec<Vector2D> {
-    let mut polygon = vec![];
-    let mut cur_pos = Vector2D::ZERO;
-    for instruction in path {
-        polygon.push(cur_pos);
-        cur_pos = cur_pos + instruction.dig_vector();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// The direction letter and amount at the start of each line.
+    Plain,
+    /// The amount and direction hidden in the colour code.
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Color(u8, u8, u8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PlanLine {
+    plain: DigInstruction,
+    hex: DigInstruction,
+    color: Color,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ParseLineError {
+    UnexpectedFormat,
+    InvalidDirection(String),
+    InvalidHexDirection(char),
+    InvalidAmount,
+}
+
+#[derive(Debug)]
+enum ParsePlanError {
+    IOError(std::io::Error),
+    /// A line that couldn't be parsed, numbered from 1.
+    InvalidLine(usize, ParseLineError),
+}
+
+impl Display for ParsePlanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(err) => write!(f, "Failed to read the plan: {}", err),
+            Self::InvalidLine(line, err) => write!(f, "Invalid line {}: {:?}", line, err),
+        }
     }
-    polygon
 }
 
-fn get_interior_area(points: &[Vector2D]) -> usize {
-    let n = points.len();
-    let mut area = 0;
+impl FromStr for PlanLine {
+    type Err = ParseLineError;
 
-    for i in 0..n {
-        let j = (i + 1) % n;
-        area += points[i].0 * points[j].1;
-        area -= points[j].0 * points[i].1;
-        // println!("{:?} <-> {:?}", points[i], points[j]);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = regex!(REGEX_TEXT);
+        let captures = regex.captures(s.trim()).ok_or(ParseLineError::UnexpectedFormat)?;
+
+        let direction = DigInstruction::parse_direction(&captures[1])?;
+        let amount = captures[2].parse::<usize>().map_err(|_| ParseLineError::InvalidAmount)?;
+        let hex = &captures[3];
+        let color_component = |i: usize| u8::from_str_radix(&hex[i..(i + 2)], 16).unwrap();
+
+        Ok(Self {
+            plain: DigInstruction { direction, amount },
+            hex: DigInstruction::from_hex_code(hex)?,
+            color: Color(color_component(0), color_component(2), color_component(4)),
+        })
     }
+}
 
-    area.abs() as usize / 2
+/// Every line of the dig plan, with both ways of reading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DigPlan {
+    lines: Vec<PlanLine>,
 }
 
-#[derive(Debug, Clone)]
+impl DigPlan {
+    /// Biggest width or height [`DigPlan::render`] will draw.
+    const MAX_RENDER_SIZE: usize = 500;
+
+    fn from_reader(reader: impl BufRead) -> Result<Self, ParsePlanError> {
+        let mut lines = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(ParsePlanError::IOError)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            lines.push(line.parse().map_err(|err| ParsePlanError::InvalidLine(i + 1, err))?);
+        }
+        Ok(Self { lines })
+    }
+
+    fn instructions(&self, encoding: Encoding) -> impl Iterator<Item = &DigInstruction> + Clone {
+        self.lines.iter().map(move |line| match encoding {
+            Encoding::Plain => &line.plain,
+            Encoding::Hex => &line.hex,
+        })
+    }
+
+    fn lagoon_area(&self, encoding: Encoding) -> i128 {
+        lagoon_area(self.instructions(encoding))
+    }
+
+    fn validate(&self, encoding: Encoding) -> Vec<PlanIssue> {
+        validate(self.instructions(encoding))
+    }
+
+    /// Draws the trench, coloured with the ANSI colour of the line that dug each tile, and the
+    /// lagoon inside of it. Returns `None` if it would be too big to be useful.
+    fn render(&self, encoding: Encoding) -> Option<String> {
+        let mut trench = vec![];
+        let mut cur_pos = (0i128, 0i128);
+        for line in &self.lines {
+            let instruction = match encoding {
+                Encoding::Plain => &line.plain,
+                Encoding::Hex => &line.hex,
+            };
+            if instruction.amount > Self::MAX_RENDER_SIZE {
+                return None;
+            }
+            let (dx, dy) = instruction.dig_vector();
+            let (dx, dy) = (dx.signum(), dy.signum());
+            for _ in 0..instruction.amount {
+                cur_pos = (cur_pos.0 + dx, cur_pos.1 + dy);
+                trench.push((cur_pos, line.color));
+            }
+        }
+
+        let min_x = trench.iter().map(|(p, _)| p.0).min()?;
+        let max_x = trench.iter().map(|(p, _)| p.0).max()?;
+        let min_y = trench.iter().map(|(p, _)| p.1).min()?;
+        let max_y = trench.iter().map(|(p, _)| p.1).max()?;
+        if max_x - min_x >= Self::MAX_RENDER_SIZE as i128 || max_y - min_y >= Self::MAX_RENDER_SIZE as i128 {
+            return None;
+        }
+
+        // A border of ground around the trench, so everything outside can be flooded from a corner
+        let width = (max_x - min_x + 3) as usize;
+        let height = (max_y - min_y + 3) as usize;
+        let mut colors = vec![None; width * height];
+        for ((x, y), color) in trench {
+            colors[(y - min_y + 1) as usize * width + (x - min_x + 1) as usize] = Some(color);
+        }
+
+        let mut outside = HashSet::new();
+        let mut pending = vec![(0, 0)];
+        while let Some((x, y)) = pending.pop() {
+            if colors[y * width + x].is_some() || !outside.insert((x, y)) {
+                continue;
+            }
+            if x > 0 { pending.push((x - 1, y)); }
+            if y > 0 { pending.push((x, y - 1)); }
+            if x + 1 < width { pending.push((x + 1, y)); }
+            if y + 1 < height { pending.push((x, y + 1)); }
+        }
+
+        let mut rendered = String::new();
+        for y in 1..(height - 1) {
+            for x in 1..(width - 1) {
+                match colors[y * width + x] {
+                    Some(Color(r, g, b)) => rendered += &format!("\x1b[38;2;{};{};{}m#\x1b[0m", r, g, b),
+                    None if outside.contains(&(x, y)) => rendered.push('.'),
+                    None => rendered.push('#'),
+                }
+            }
+            rendered.push('\n');
+        }
+        Some(rendered)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct DigInstruction {
     direction: Direction,
     amount: usize,
 }
 
 impl DigInstruction {
-    fn from_hex_code(s: &str) -> Self {
-        let regex = regex!(REGEX_TEXT);
-        let captures = regex.captures(s).ok_or("Regex failed").unwrap();
-        let hex = captures.get(3).unwrap().as_str();
-        let direction = match &hex[5..] {
-            "0" => Direction::East,
-            "1" => Direction::South,
-            "2" => Direction::West,
-            "3" => Direction::North,
-            _ => panic!("Unexpected case: {}", &hex[5..]),
+    /// Reads the six hex digits of a colour code, where the first five are the amount and the
+    /// last one is the direction.
+    fn from_hex_code(hex: &str) -> Result<Self, ParseLineError> {
+        let direction_digit = hex.chars().nth(5).ok_or(ParseLineError::UnexpectedFormat)?;
+        let direction = match direction_digit {
+            '0' => Direction::East,
+            '1' => Direction::South,
+            '2' => Direction::West,
+            '3' => Direction::North,
+            _ => return Err(ParseLineError::InvalidHexDirection(direction_digit)),
         };
-        let amount = usize::from_str_radix(&hex[..5], 16).unwrap();
-        Self {
+        let amount = usize::from_str_radix(&hex[..5], 16).map_err(|_| ParseLineError::InvalidAmount)?;
+        Ok(Self {
             direction,
             amount,
-        }
+        })
     }
 
-    fn parse_direction(s: &str) -> Direction {
+    fn parse_direction(s: &str) -> Result<Direction, ParseLineError> {
         match s {
-            "U" => Direction::North,
-            "R" => Direction::East,
-            "D" => Direction::South,
-            "L" => Direction::West,
-            _ => panic!("Invalid direction {}", s),
+            "U" => Ok(Direction::North),
+            "R" => Ok(Direction::East),
+            "D" => Ok(Direction::South),
+            "L" => Ok(Direction::West),
+            _ => Err(ParseLineError::InvalidDirection(s.to_owned())),
         }
     }
-    
-    fn dig_vector(&self) -> Vector2D {
-        Vector2D::from(self.direction) * self.amount.clone()
-    }
-}
-
-impl FromStr for DigInstruction {
-    type Err = &'static str;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let regex = regex!(REGEX_TEXT);
-        let captures = regex.captures(s).ok_or("Regex failed")?;
-        let direction = DigInstruction::parse_direction(
-            captures.get(1).ok_or("No direction in match")?.as_str(),
-        );
-        let amount = captures.get(2).ok_or("No direction in match")?.as_str()
-            .parse::<usize>().unwrap();
-        Ok(Self {
-            direction,
-            amount,
-        })
+    fn dig_vector(&self) -> (i128, i128) {
+        let amount = self.amount as i128;
+        match self.direction {
+            Direction::North => (0, -amount),
+            Direction::East => (amount, 0),
+            Direction::South => (0, amount),
+            Direction::West => (-amount, 0),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use aoc_shared::direction::Direction;
     use aoc_shared::vector2d::Vector2D;
-    use crate::get_interior_area;
+    use crate::{DigInstruction, DigPlan, Encoding, get_interior_area, ParseLineError, PlanIssue, PlanLine};
+
+    const EXAMPLE: &str = "\
+R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)
+";
 
     #[test]
     fn get_simple_area() {
-        let polygon = &[Vector2D(0, 0), Vector2D(2, 0), Vector2D(2, 1), Vector2D(0, 1)];
+        let polygon = &[(0, 0), (2, 0), (2, 1), (0, 1)];
 
         let area = get_interior_area(polygon);
 
         assert_eq!(area, 2);
     }
+
+    #[test]
+    fn calculates_both_parts_from_one_parse() {
+        let plan = DigPlan::from_reader(EXAMPLE.as_bytes()).unwrap();
+
+        assert_eq!(plan.lagoon_area(Encoding::Plain), 62);
+        assert_eq!(plan.lagoon_area(Encoding::Hex), 952408144115);
+    }
+
+    #[test]
+    fn does_not_overflow_on_huge_plans() {
+        // A square whose sides are 4000 lines of 0xfffff each, so both the shoelace products and
+        // the area itself are past i64::MAX.
+        let repeats = 4000;
+        let plan = ["R 1 (#fffff0)\n", "D 1 (#fffff1)\n", "L 1 (#fffff2)\n", "U 1 (#fffff3)\n"]
+            .map(|line| line.repeat(repeats))
+            .concat();
+        let plan = DigPlan::from_reader(plan.as_bytes()).unwrap();
+
+        let side_len = 0xfffff_i128 * repeats as i128;
+        assert!(side_len * side_len > i64::MAX as i128);
+        assert_eq!(plan.lagoon_area(Encoding::Hex), (side_len + 1) * (side_len + 1));
+    }
+
+    #[test]
+    fn parses_hex_code() {
+        let line: PlanLine = "R 6 (#70c710)".parse().unwrap();
+
+        assert_eq!(line.hex, DigInstruction { direction: Direction::East, amount: 461937 });
+        assert_eq!(line.plain, DigInstruction { direction: Direction::East, amount: 6 });
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        assert_eq!("R 6 (#70c714)".parse::<PlanLine>(), Err(ParseLineError::InvalidHexDirection('4')));
+        assert_eq!("X 6 (#70c710)".parse::<PlanLine>(), Err(ParseLineError::InvalidDirection("X".to_owned())));
+        assert_eq!("R 6 #70c710".parse::<PlanLine>(), Err(ParseLineError::UnexpectedFormat));
+        assert!(DigPlan::from_reader("R 6 (#70c710)\nR 6 (#zzzzzz)".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn renders_filled_lagoon() {
+        let plan = DigPlan::from_reader(EXAMPLE.as_bytes()).unwrap();
+
+        let rendered = plan.render(Encoding::Plain).unwrap();
+        let without_colors = rendered
+            .replace("\x1b[0m", "")
+            .split("\x1b[")
+            .map(|s| s.split_once('m').map_or(s, |(_, rest)| rest))
+            .collect::<String>();
+
+        let expected = "\
+#######
+#######
+#######
+..#####
+..#####
+#######
+#####..
+#######
+.######
+.######
+";
+        assert_eq!(without_colors, expected);
+        assert_eq!(without_colors.matches('#').count(), 62);
+        // The origin is dug last, by "U 2 (#7a21e3)"
+        assert!(rendered.starts_with("\x1b[38;2;122;33;227m#"));
+    }
+
+    #[test]
+    fn does_not_render_huge_plans() {
+        let plan = DigPlan::from_reader(EXAMPLE.as_bytes()).unwrap();
+
+        assert!(plan.render(Encoding::Hex).is_none());
+    }
+
+    fn plain_plan(s: &str) -> DigPlan {
+        let lines: String = s
+            .split(',')
+            .map(|step| format!("{} (#000000)\n", step.trim()))
+            .collect();
+        DigPlan::from_reader(lines.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn accepts_example_plans() {
+        let plan = DigPlan::from_reader(EXAMPLE.as_bytes()).unwrap();
+
+        assert_eq!(plan.validate(Encoding::Plain), vec![]);
+        assert_eq!(plan.validate(Encoding::Hex), vec![]);
+    }
+
+    #[test]
+    fn reports_plans_that_do_not_close() {
+        let plan = plain_plan("R 2, D 2, L 2");
+
+        assert_eq!(plan.validate(Encoding::Plain), vec![PlanIssue::NotClosed { end: Vector2D(0, 2) }]);
+    }
+
+    #[test]
+    fn reports_backtracking() {
+        let plan = plain_plan("R 4, L 2, D 2, L 2, U 2");
+
+        // Going back also leaves the third instruction starting in the middle of the first one
+        assert_eq!(plan.validate(Encoding::Plain), vec![
+            PlanIssue::Backtracks { instruction: 1 },
+            PlanIssue::SelfIntersects { first: 0, second: 2 },
+        ]);
+    }
+
+    #[test]
+    fn reports_self_intersections() {
+        // A figure eight that crosses itself at (2, 0)
+        let plan = plain_plan("R 4, D 2, L 2, U 4, L 2, D 2");
+
+        assert_eq!(plan.validate(Encoding::Plain), vec![PlanIssue::SelfIntersects { first: 0, second: 3 }]);
+    }
+
+    #[test]
+    fn reports_overlaps() {
+        // The fifth instruction digs back along the first one between x = 3 and x = 2
+        let plan = plain_plan("R 4, D 2, L 1, U 2, L 1, D 2, L 2, U 2");
+
+        assert_eq!(plan.validate(Encoding::Plain), vec![
+            PlanIssue::SelfIntersects { first: 0, second: 3 },
+            PlanIssue::Overlaps { first: 0, second: 4 },
+            PlanIssue::SelfIntersects { first: 0, second: 5 },
+        ]);
+    }
 }
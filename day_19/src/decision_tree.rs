@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use crate::{Condition, Destination, Workflow};
+use crate::part::Part;
+use crate::workflow_map::WorkflowMap;
+use crate::rating_box::RatingBox;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecisionNode {
+    Accept,
+    Reject,
+    Branch {
+        condition: Condition,
+        pass: Box<DecisionNode>,
+        fail: Box<DecisionNode>,
+    },
+}
+
+impl DecisionNode {
+    /// Builds a branch, collapsing it when both sides lead to the same outcome.
+    fn branch(condition: Condition, pass: DecisionNode, fail: DecisionNode) -> Self {
+        if pass == fail {
+            return pass;
+        }
+        Self::Branch {
+            condition,
+            pass: Box::new(pass),
+            fail: Box::new(fail),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::Accept | Self::Reject => 0,
+            Self::Branch { pass, fail, .. } => 1 + pass.depth().max(fail.depth()),
+        }
+    }
+
+    fn collect_accepted(&self, ranges: Vec<RatingBox>, accepted: &mut Vec<RatingBox>) {
+        match self {
+            Self::Accept => accepted.extend(ranges),
+            Self::Reject => {}
+            Self::Branch { condition, pass, fail } => {
+                let (matching, remaining) = RatingBox::split_all(&ranges, condition);
+                pass.collect_accepted(matching, accepted);
+                fail.collect_accepted(remaining, accepted);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CompileError {
+    UnknownWorkflow(String),
+    Cycle(String),
+    NoFallback(String),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownWorkflow(id) => write!(f, "workflow \"{id}\" does not exist"),
+            Self::Cycle(id) => write!(f, "workflow \"{id}\" can send parts back to itself"),
+            Self::NoFallback(id) => write!(f, "workflow \"{id}\" has no rule for some parts"),
+        }
+    }
+}
+
+/// Identifies a rule by the workflow it belongs to and its position inside it.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct RuleId {
+    pub workflow: String,
+    pub index: usize,
+}
+
+impl Display for RuleId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}#{}", self.workflow, self.index)
+    }
+}
+
+/// The workflows flattened into binary decisions, with every branch that can't be taken for
+/// ratings inside the compiled domain pruned away.
+#[derive(Debug)]
+pub struct DecisionTree {
+    root: DecisionNode,
+    domain: RatingBox,
+    unreachable_rules: Vec<RuleId>,
+    unreachable_workflows: Vec<String>,
+}
+
+impl DecisionTree {
+    pub fn compile(map: &WorkflowMap, domain: RangeInclusive<usize>) -> Result<Self, CompileError> {
+        let domain = RatingBox::from_range(domain, map.schema().len());
+        let mut compiler = Compiler {
+            map,
+            path: Vec::new(),
+            entered: HashSet::new(),
+            applied: HashSet::new(),
+        };
+        let root = compiler.compile_workflow(vec![domain.clone()], map.starting_workflow())?;
+
+        let mut unreachable_rules = map.workflows()
+            .flat_map(|w| (0..w.rules.len()).map(|index| (w.id.as_str(), index)))
+            .filter(|rule| !compiler.applied.contains(rule))
+            .map(|(workflow, index)| RuleId { workflow: workflow.to_owned(), index })
+            .collect::<Vec<_>>();
+        unreachable_rules.sort();
+        let mut unreachable_workflows = map.workflows()
+            .filter(|w| !compiler.entered.contains(w.id.as_str()))
+            .map(|w| w.id.clone())
+            .collect::<Vec<_>>();
+        unreachable_workflows.sort();
+
+        Ok(Self {
+            root,
+            domain,
+            unreachable_rules,
+            unreachable_workflows,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.root.depth()
+    }
+
+    /// Classifies a part by walking a single branch of the tree. Parts with ratings outside the
+    /// compiled domain may be misclassified, as checks that always pass inside it are pruned.
+    pub fn classify(&self, part: &Part) -> bool {
+        let mut node = &self.root;
+        loop {
+            match node {
+                DecisionNode::Accept => return true,
+                DecisionNode::Reject => return false,
+                DecisionNode::Branch { condition, pass, fail } => {
+                    node = if condition.check(part) { pass } else { fail };
+                }
+            }
+        }
+    }
+
+    /// Disjoint boxes of ratings that end up accepted.
+    pub fn accepted_ranges(&self) -> Vec<RatingBox> {
+        let mut accepted = Vec::new();
+        self.root.collect_accepted(vec![self.domain.clone()], &mut accepted);
+        accepted
+    }
+
+    pub fn accepted_combinations(&self) -> usize {
+        self.accepted_ranges().iter().map(RatingBox::combinations_count).sum()
+    }
+
+    /// Rules that never match any part by the time it reaches them.
+    pub fn unreachable_rules(&self) -> &[RuleId] {
+        &self.unreachable_rules
+    }
+
+    pub fn unreachable_workflows(&self) -> &[String] {
+        &self.unreachable_workflows
+    }
+}
+
+struct Compiler<'a> {
+    map: &'a WorkflowMap,
+    path: Vec<&'a str>,
+    entered: HashSet<&'a str>,
+    applied: HashSet<(&'a str, usize)>,
+}
+
+impl<'a> Compiler<'a> {
+    fn compile_destination(
+        &mut self,
+        ranges: Vec<RatingBox>,
+        destination: &'a Destination,
+    ) -> Result<DecisionNode, CompileError> {
+        match destination {
+            Destination::Accept => Ok(DecisionNode::Accept),
+            Destination::Reject => Ok(DecisionNode::Reject),
+            Destination::SendTo(id) => {
+                let workflow = self.map.get(id)
+                    .ok_or_else(|| CompileError::UnknownWorkflow(id.clone()))?;
+                self.compile_workflow(ranges, workflow)
+            }
+        }
+    }
+
+    fn compile_workflow(
+        &mut self,
+        ranges: Vec<RatingBox>,
+        workflow: &'a Workflow,
+    ) -> Result<DecisionNode, CompileError> {
+        if self.path.contains(&workflow.id.as_str()) {
+            return Err(CompileError::Cycle(workflow.id.clone()));
+        }
+        self.path.push(&workflow.id);
+        self.entered.insert(&workflow.id);
+        let node = self.compile_rules(ranges, workflow, 0);
+        self.path.pop();
+        node
+    }
+
+    fn compile_rules(
+        &mut self,
+        ranges: Vec<RatingBox>,
+        workflow: &'a Workflow,
+        index: usize,
+    ) -> Result<DecisionNode, CompileError> {
+        if RatingBox::total_combinations(&ranges) == 0 {
+            return Ok(DecisionNode::Reject);
+        }
+        let rule = workflow.rules.get(index)
+            .ok_or_else(|| CompileError::NoFallback(workflow.id.clone()))?;
+        let Some(condition) = rule.condition() else {
+            self.applied.insert((&workflow.id, index));
+            return self.compile_destination(ranges, rule.destination());
+        };
+
+        let (matching, remaining) = RatingBox::split_all(&ranges, condition);
+        if RatingBox::total_combinations(&matching) == 0 {
+            return self.compile_rules(remaining, workflow, index + 1);
+        }
+        self.applied.insert((&workflow.id, index));
+        let pass = self.compile_destination(matching, rule.destination())?;
+        if RatingBox::total_combinations(&remaining) == 0 {
+            return Ok(pass);
+        }
+        let fail = self.compile_rules(remaining, workflow, index + 1)?;
+        Ok(DecisionNode::branch(*condition, pass, fail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use aoc_shared::rng::Lcg;
+    use crate::decision_tree::{CompileError, DecisionNode, DecisionTree, RuleId};
+    use crate::part::Part;
+    use crate::schema::Schema;
+    use crate::workflow_map::WorkflowMap;
+
+    const EXAMPLE: &str = "\
+px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+";
+
+    fn read(input: &str) -> WorkflowMap {
+        WorkflowMap::from_lines(&mut input.as_bytes().lines(), Schema::xmas())
+    }
+
+    #[test]
+    fn accepted_ranges_match_combinations() {
+        let workflow = read(EXAMPLE);
+        let tree = DecisionTree::compile(&workflow, 1..=4000).unwrap();
+
+        assert_eq!(tree.accepted_combinations(), 167409079868000);
+        assert_eq!(tree.accepted_combinations(), workflow.calculate_combinations(1..=4000).unwrap());
+    }
+
+    #[test]
+    fn classifies_like_workflows() {
+        let workflow = read(EXAMPLE);
+        let tree = DecisionTree::compile(&workflow, 1..=4000).unwrap();
+
+        let mut rng = Lcg::new(7);
+        for _ in 0..2000 {
+            let mut ratings = [0; 4];
+            for rating in ratings.iter_mut() {
+                *rating = rng.below(4000) as usize + 1;
+            }
+            let part = Part::new(ratings);
+            assert_eq!(tree.classify(&part), workflow.check_accepted(&part), "{part:?}");
+        }
+    }
+
+    #[test]
+    fn merges_branches_with_the_same_outcome() {
+        let tree = DecisionTree::compile(&read("in{a<5:A,A}"), 1..=4000).unwrap();
+
+        assert_eq!(tree.root, DecisionNode::Accept);
+        assert_eq!(tree.depth(), 0);
+    }
+
+    #[test]
+    fn compiles_without_properties() {
+        let map = WorkflowMap::from_lines(&mut "in{A}\n".as_bytes().lines(), Schema::inferred());
+        let tree = DecisionTree::compile(&map, 1..=4000).unwrap();
+
+        assert_eq!(tree.root, DecisionNode::Accept);
+        assert_eq!(tree.accepted_combinations(), 1);
+    }
+
+    #[test]
+    fn detects_unreachable_rules_and_workflows() {
+        let input = "in{x<10:lo,hi}\nlo{x>20:R,A}\nhi{x>5:A,R}\nunused{A}\n";
+        let tree = DecisionTree::compile(&read(input), 1..=4000).unwrap();
+
+        let rule = |workflow: &str, index| RuleId { workflow: workflow.to_owned(), index };
+        assert_eq!(tree.unreachable_rules(), &[rule("hi", 1), rule("lo", 0), rule("unused", 0)]);
+        assert_eq!(tree.unreachable_workflows(), &["unused".to_owned()]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let result = DecisionTree::compile(&read("in{x<10:a,A}\na{m<10:in,R}\n"), 1..=4000);
+
+        assert_eq!(result.unwrap_err(), CompileError::Cycle("in".to_owned()));
+    }
+}
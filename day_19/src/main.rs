@@ -1,39 +1,92 @@
 mod part;
 mod workflow_map;
 mod rule;
-mod xmas_range;
+mod rating_box;
+mod decision_tree;
+mod schema;
 
 use std::cmp::Ordering;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::str::FromStr;
 use regex_macro::regex;
+use crate::decision_tree::DecisionTree;
 use crate::part::Part;
 use crate::rule::Rule;
+use crate::schema::Schema;
 use crate::workflow_map::WorkflowMap;
 
 fn main() {
-    part_1();
+    let (workflow, parts) = read_input();
+    println!("Properties: {}", workflow.schema().names().join(", "));
+    part_1(&workflow, &parts);
+    part_2(&workflow);
+    analyse(&workflow);
 }
 
-fn part_1() {
+fn read_input() -> (WorkflowMap, Vec<Part>) {
     let reader = read_file();
     let mut lines = reader.lines();
-    let workflow = WorkflowMap::from_lines(&mut lines);
+    let mut workflow = WorkflowMap::from_lines(&mut lines, Schema::inferred());
+    let parts = lines
+        .map(|line| workflow.parse_part(&line.unwrap()).unwrap())
+        .collect();
+    (workflow, parts)
+}
+
+fn part_1(workflow: &WorkflowMap, parts: &[Part]) {
+    println!("Sum: {}", accepted_ratings_sum(workflow, parts));
+}
+
+/// Sums the ratings of accepted parts, classifying them with a tree compiled over every rating
+/// the parts use so that no check they depend on is pruned.
+fn accepted_ratings_sum(workflow: &WorkflowMap, parts: &[Part]) -> usize {
+    let max_rating = parts.iter().map(Part::max_rating).max().unwrap_or(0);
+    let tree = DecisionTree::compile(workflow, 0..=max_rating).unwrap();
 
     let mut sum = 0;
-    for line_result in lines {
-        let part = line_result.unwrap().parse::<Part>().unwrap();
+    for part in parts {
         // print!("{:?} = ", part);
-        if workflow.check_accepted(&part) {
+        let accepted = tree.classify(part);
+        debug_assert_eq!(accepted, workflow.check_accepted(part));
+        if accepted {
             // println!("Accepted");
             sum += part.values_sum();
         } else {
             // println!("Rejected");
         }
     }
-    println!("Sum: {sum}");
+    sum
+}
+
+fn part_2(workflow: &WorkflowMap) {
+    match workflow.calculate_combinations(1..=4000) {
+        Ok(combinations) => println!("Accepted combinations: {combinations}"),
+        Err(err) => println!("Could not count combinations: {err}"),
+    }
+}
+
+fn analyse(workflow: &WorkflowMap) {
+    let compile = |workflow: &WorkflowMap| DecisionTree::compile(workflow, 1..=4000)
+        .map_err(|err| println!("Could not compile workflows: {err}"))
+        .ok();
+    let Some(tree) = compile(&workflow.simplified()) else {
+        return;
+    };
+    println!("Decision tree depth: {}", tree.depth());
+    println!("Accepted boxes: {} ({} combinations)", tree.accepted_ranges().len(), tree.accepted_combinations());
+
+    // Simplifying drops and inlines rules, so only the original map says which of them are dead.
+    let Some(tree) = compile(workflow) else {
+        return;
+    };
+    for rule in tree.unreachable_rules() {
+        println!("Rule never applies: {rule}");
+    }
+    for id in tree.unreachable_workflows() {
+        println!("Workflow never reached: {id}");
+    }
 }
 
 fn read_file() -> impl BufRead {
@@ -44,32 +97,29 @@ fn read_file() -> impl BufRead {
 }
 
 pub const STARTING_POINT: &str = "in";
-pub const PROPERTIES_COUNT: usize = 4;
-
-pub type XMAS = [usize; PROPERTIES_COUNT];
-
-#[derive(Debug, Copy, Clone)]
-pub enum Property {
-    X = 0,
-    M = 1,
-    A = 2,
-    S = 3,
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    UnexpectedFormat(String),
+    UnknownProperty(String),
+    MissingProperty(String),
+    InvalidValue(String),
 }
 
-impl FromStr for Property {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "x" => Ok(Property::X),
-            "m" => Ok(Property::M),
-            "a" => Ok(Property::A),
-            "s" => Ok(Property::S),
-            _ => panic!("Not a property name: {}", s)
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedFormat(s) => write!(f, "unexpected format: \"{s}\""),
+            Self::UnknownProperty(name) => write!(f, "unknown property \"{name}\""),
+            Self::MissingProperty(name) => write!(f, "missing property \"{name}\""),
+            Self::InvalidValue(s) => write!(f, "invalid value: \"{s}\""),
         }
     }
 }
 
+/// Index of a rating inside a part, resolved by name through a [`Schema`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Property(pub usize);
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Destination {
     Accept,
@@ -87,37 +137,86 @@ impl From<&str> for Destination {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct Condition(pub Property, pub Ordering, pub usize);
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operator {
+    /// Whether a rating comparing as `ordering` against the condition value passes.
+    pub fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            Self::Less => ordering.is_lt(),
+            Self::LessOrEqual => ordering.is_le(),
+            Self::Greater => ordering.is_gt(),
+            Self::GreaterOrEqual => ordering.is_ge(),
+            Self::Equal => ordering.is_eq(),
+            Self::NotEqual => ordering.is_ne(),
+        }
+    }
 
-impl Condition {
     pub fn inverted(&self) -> Self {
-        match self.1 {
-            Ordering::Less => Condition(self.0, Ordering::Greater, self.2 - 1),
-            Ordering::Greater => Condition(self.0, Ordering::Less, self.2 + 1),
-            Ordering::Equal => panic!("Cannot invert equals."),
+        match self {
+            Self::Less => Self::GreaterOrEqual,
+            Self::LessOrEqual => Self::Greater,
+            Self::Greater => Self::LessOrEqual,
+            Self::GreaterOrEqual => Self::Less,
+            Self::Equal => Self::NotEqual,
+            Self::NotEqual => Self::Equal,
         }
     }
 }
 
-impl FromStr for Condition {
+impl FromStr for Operator {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let condition_regex = regex!(r"(\w+)([<>])(\d+)");
-        let captures = condition_regex.captures(s).unwrap();
-        let property = captures.get(1).unwrap().as_str().parse::<Property>()?;
-        let operator = match captures.get(2).unwrap().as_str() {
-            "<" => Ordering::Less,
-            ">" => Ordering::Greater,
-            &_ => panic!("Unexpected condition operator")
-        };
-        let value = captures.get(3).unwrap().as_str().parse::<usize>().unwrap();
+        match s {
+            "<" => Ok(Self::Less),
+            "<=" => Ok(Self::LessOrEqual),
+            ">" => Ok(Self::Greater),
+            ">=" => Ok(Self::GreaterOrEqual),
+            "=" => Ok(Self::Equal),
+            "!=" => Ok(Self::NotEqual),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Condition(pub Property, pub Operator, pub usize);
+
+impl Condition {
+    /// Parts are checked for every rating of the schema when parsed, so a part without the
+    /// rating can only come from a different schema, and never matches.
+    pub fn check(&self, part: &Part) -> bool {
+        part.get(self.0).is_some_and(|rating| self.1.accepts(rating.cmp(&self.2)))
+    }
+
+    pub fn inverted(&self) -> Self {
+        Condition(self.0, self.1.inverted(), self.2)
+    }
+}
+
+impl Condition {
+    pub fn parse(s: &str, schema: &mut Schema) -> Result<Self, ParseError> {
+        let condition_regex = regex!(r"^(\w+)(<=|>=|!=|<|>|=)(\d+)$");
+        let captures = condition_regex.captures(s)
+            .ok_or_else(|| ParseError::UnexpectedFormat(s.to_owned()))?;
+        let property = schema.resolve(captures.get(1).unwrap().as_str())?;
+        let operator = captures.get(2).unwrap().as_str().parse::<Operator>().unwrap();
+        let value = captures.get(3).unwrap().as_str();
+        let value = value.parse::<usize>().map_err(|_| ParseError::InvalidValue(value.to_owned()))?;
         Ok(Self(property, operator, value))
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Workflow {
     id: String,
     rules: Vec<Rule>,
@@ -125,20 +224,21 @@ pub struct Workflow {
 
 impl Workflow {
     fn get_destination_for_part(&self, part: &Part) -> Option<&Destination> {
-        self.rules.iter().flat_map(|r| r.get_destination_for_part(&part)).next()
+        self.rules.iter().flat_map(|r| r.get_destination_for_part(part)).next()
     }
 }
 
-impl FromStr for Workflow {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Workflow {
+    pub fn parse(s: &str, schema: &mut Schema) -> Result<Self, ParseError> {
         let workflow_regex = regex!(r"(\w+)\{(.*)\}");
-        let captures = workflow_regex.captures(s).unwrap();
+        let captures = workflow_regex.captures(s)
+            .ok_or_else(|| ParseError::UnexpectedFormat(s.to_owned()))?;
         let id = captures.get(1).unwrap().as_str().to_owned();
 
         let payload = captures.get(2).unwrap().as_str();
-        let rules = payload.split(',').map(Rule::from_str).collect::<Result<Vec<_>, _>>()?;
+        let rules = payload.split(',')
+            .map(|rule| Rule::parse(rule, schema))
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(Self {
             id,
             rules,
@@ -148,13 +248,15 @@ impl FromStr for Workflow {
 
 #[cfg(test)]
 mod tests {
-    use std::cmp::Ordering;
-    use crate::{Condition, Destination, Property, Rule, Part};
+    use std::io::BufRead;
+    use crate::{accepted_ratings_sum, Condition, Destination, Operator, Property, Rule, Part};
+    use crate::schema::Schema;
+    use crate::workflow_map::WorkflowMap;
 
     #[test]
     fn check_condition_pass() {
-        let part = Part::new(787, 2655, 1222, 2876);
-        let rule = Rule::with_condition(Condition(Property::A, Ordering::Less, 2006), Destination::SendTo("qkq".to_owned()));
+        let part = Part::new([787, 2655, 1222, 2876]);
+        let rule = Rule::with_condition(Condition(Property(2), Operator::Less, 2006), Destination::SendTo("qkq".to_owned()));
 
         let result = rule.get_destination_for_part(&part);
 
@@ -164,11 +266,49 @@ mod tests {
 
     #[test]
     fn check_condition_fail() {
-        let part = Part::new(787000, 2655, 1222, 2876);
-        let rule = Rule::with_condition(Condition(Property::A, Ordering::Less, 2006), Destination::SendTo("qkq".to_owned()));
+        let part = Part::new([787, 2655, 2006, 2876]);
+        let rule = Rule::with_condition(Condition(Property(2), Operator::Less, 2006), Destination::SendTo("qkq".to_owned()));
 
         let result = rule.get_destination_for_part(&part);
 
         assert!(result.is_none());
     }
+
+    #[test]
+    fn parses_every_operator() {
+        let mut schema = Schema::xmas();
+        let mut parse = |s: &str| Condition::parse(s, &mut schema).unwrap();
+
+        assert_eq!(parse("x<10"), Condition(Property(0), Operator::Less, 10));
+        assert_eq!(parse("m<=10"), Condition(Property(1), Operator::LessOrEqual, 10));
+        assert_eq!(parse("a>10"), Condition(Property(2), Operator::Greater, 10));
+        assert_eq!(parse("s>=10"), Condition(Property(3), Operator::GreaterOrEqual, 10));
+        assert_eq!(parse("x=10"), Condition(Property(0), Operator::Equal, 10));
+        assert_eq!(parse("x!=10"), Condition(Property(0), Operator::NotEqual, 10));
+        assert!(Condition::parse("x=>10", &mut Schema::xmas()).is_err());
+        assert!(Condition::parse("y<10", &mut Schema::xmas()).is_err());
+    }
+
+    #[test]
+    fn inverted_conditions_check_the_complement() {
+        let operators = [
+            Operator::Less, Operator::LessOrEqual, Operator::Greater,
+            Operator::GreaterOrEqual, Operator::Equal, Operator::NotEqual,
+        ];
+        for operator in operators {
+            let condition = Condition(Property(1), operator, 5);
+            for m in 3..=7 {
+                let part = Part::new([0, m, 0, 0]);
+                assert_ne!(condition.check(&part), condition.inverted().check(&part), "{operator:?} {m}");
+            }
+        }
+    }
+
+    #[test]
+    fn classifies_parts_rated_above_the_puzzle_range() {
+        let workflow = WorkflowMap::from_lines(&mut "in{x>5000:R,A}\n".as_bytes().lines(), Schema::xmas());
+        let parts = [Part::new([6000, 1, 1, 1]), Part::new([10, 1, 1, 1])];
+
+        assert_eq!(accepted_ratings_sum(&workflow, &parts), 13);
+    }
 }
@@ -1,45 +1,61 @@
 use std::fmt::{Debug, Formatter};
-use std::str::FromStr;
-use crate::{Property, XMAS};
+use crate::{ParseError, Property};
+use crate::schema::Schema;
 
 #[derive(Default)]
-pub struct Part(XMAS);
+pub struct Part(Vec<usize>);
 
 impl Debug for Part {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{{x={},m={},a={},s={}}}", self.0[0], self.0[1], self.0[2], self.0[3])
+        write!(f, "{:?}", self.0)
     }
 }
 
 impl Part {
-    pub fn new(x: usize, m: usize, a: usize, s: usize) -> Self {
-        Self([x, m, a, s])
+    /// A part with one rating per property, in schema order.
+    pub fn new(ratings: impl Into<Vec<usize>>) -> Self {
+        Self(ratings.into())
     }
 
-    pub fn get(&self, property: Property) -> usize {
-        self.0[property as usize]
+    pub fn get(&self, property: Property) -> Option<usize> {
+        self.0.get(property.0).copied()
     }
 
     pub fn set(&mut self, property: Property, value: usize) {
-        self.0[property as usize] = value;
+        if self.0.len() <= property.0 {
+            self.0.resize(property.0 + 1, 0);
+        }
+        self.0[property.0] = value;
     }
 
     pub fn values_sum(&self) -> usize {
         self.0.iter().sum()
     }
-}
 
-impl FromStr for Part {
-    type Err = ();
+    pub fn max_rating(&self) -> usize {
+        self.0.iter().copied().max().unwrap_or(0)
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    /// Parses a part that lists every property already in the schema. An inferred schema also
+    /// picks up any new names the part lists.
+    pub fn parse(s: &str, schema: &mut Schema) -> Result<Self, ParseError> {
         let trimmed = s.trim_start_matches('{').trim_end_matches('}');
         let mut part = Part::default();
+        let mut listed = vec![false; schema.len()];
         for prop_value in trimmed.split(',') {
             let mut prop_value_split = prop_value.split('=');
-            let property = prop_value_split.next().unwrap().parse::<Property>()?;
-            let value = prop_value_split.next().unwrap().parse::<usize>().unwrap();
+            let property = schema.resolve(prop_value_split.next().unwrap())?;
+            let value = prop_value_split.next()
+                .ok_or_else(|| ParseError::UnexpectedFormat(prop_value.to_owned()))?;
+            let value = value.parse::<usize>().map_err(|_| ParseError::InvalidValue(value.to_owned()))?;
             part.set(property, value);
+            if listed.len() <= property.0 {
+                listed.resize(property.0 + 1, false);
+            }
+            listed[property.0] = true;
+        }
+        if let Some(missing) = listed.iter().position(|&l| !l) {
+            return Err(ParseError::MissingProperty(schema.name(Property(missing)).to_owned()));
         }
         Ok(part)
     }
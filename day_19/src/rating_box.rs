@@ -0,0 +1,164 @@
+use std::cmp::{max, min, Ordering};
+use std::ops::RangeInclusive;
+use crate::{Condition, Property};
+
+/// One range of ratings per property of the schema.
+#[derive(Debug, Clone)]
+pub struct RatingBox(Vec<RangeInclusive<usize>>);
+
+impl RatingBox {
+    pub fn from_range(range: RangeInclusive<usize>, properties: usize) -> Self {
+        Self(vec![range; properties])
+    }
+
+    pub fn get(&self, property: Property) -> &RangeInclusive<usize> {
+        self.0.get(property.0).unwrap()
+    }
+
+    /// Splits the ranges into the boxes that pass the condition and the boxes that don't. The
+    /// property's interval is cut into at most three pieces (below, at and above the value), and
+    /// contiguous pieces with the same outcome are merged back together.
+    pub fn split(&self, condition: &Condition) -> (Vec<Self>, Vec<Self>) {
+        let &Condition(property, operator, value) = condition;
+        let range = self.get(property);
+        let (start, end) = (*range.start(), *range.end());
+        let pieces = [
+            (Ordering::Less, value.checked_sub(1).map(|below| start..=min(end, below))),
+            (Ordering::Equal, Some(max(start, value)..=min(end, value))),
+            (Ordering::Greater, value.checked_add(1).map(|above| max(start, above)..=end)),
+        ];
+
+        let mut merged: Vec<(bool, RangeInclusive<usize>)> = Vec::with_capacity(3);
+        for (ordering, piece) in pieces {
+            let Some(piece) = piece.filter(|p| !p.is_empty()) else {
+                continue;
+            };
+            let accepted = operator.accepts(ordering);
+            match merged.last_mut() {
+                Some((last_accepted, last)) if *last_accepted == accepted => {
+                    *last = *last.start()..=*piece.end();
+                }
+                _ => merged.push((accepted, piece)),
+            }
+        }
+
+        let mut matching = Vec::new();
+        let mut remaining = Vec::new();
+        for (accepted, piece) in merged {
+            let mut new_range = self.clone();
+            new_range.0[property.0] = piece;
+            if accepted {
+                matching.push(new_range);
+            } else {
+                remaining.push(new_range);
+            }
+        }
+        (matching, remaining)
+    }
+
+    /// Splits every box in `ranges`, see [`RatingBox::split`].
+    pub fn split_all(ranges: &[Self], condition: &Condition) -> (Vec<Self>, Vec<Self>) {
+        let mut matching = Vec::new();
+        let mut remaining = Vec::new();
+        for range in ranges {
+            let (range_matching, range_remaining) = range.split(condition);
+            matching.extend(range_matching);
+            remaining.extend(range_remaining);
+        }
+        (matching, remaining)
+    }
+
+    pub fn total_combinations(ranges: &[Self]) -> usize {
+        ranges.iter().map(Self::combinations_count).sum()
+    }
+
+    pub fn combinations_count(&self) -> usize {
+        self.0.iter()
+            .map(|r| if r.end() >= r.start() {
+                r.end() + 1 - r.start()
+            } else {
+                0
+            })
+            .product()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Condition, Operator, Property};
+    use crate::rating_box::RatingBox;
+
+    fn split_x(operator: Operator, value: usize) -> (Vec<RatingBox>, Vec<RatingBox>) {
+        RatingBox::from_range(1..=4000, 4).split(&Condition(Property(0), operator, value))
+    }
+
+    fn x_ranges(ranges: &[RatingBox]) -> Vec<std::ops::RangeInclusive<usize>> {
+        ranges.iter().map(|r| r.get(Property(0)).clone()).collect()
+    }
+
+    #[test]
+    fn applies_greater_condition_correctly() {
+        let (matching, remaining) = split_x(Operator::Greater, 41);
+
+        assert_eq!(x_ranges(&matching), [42..=4000]);
+        assert_eq!(x_ranges(&remaining), [1..=41]);
+        assert_eq!(*matching[0].get(Property(1)), 1..=4000);
+        assert_eq!(*matching[0].get(Property(2)), 1..=4000);
+        assert_eq!(*matching[0].get(Property(3)), 1..=4000);
+    }
+
+    #[test]
+    fn applies_lesser_condition_correctly() {
+        let (matching, remaining) = split_x(Operator::Less, 420);
+
+        assert_eq!(x_ranges(&matching), [1..=419]);
+        assert_eq!(x_ranges(&remaining), [420..=4000]);
+        assert_eq!(*matching[0].get(Property(1)), 1..=4000);
+        assert_eq!(*matching[0].get(Property(2)), 1..=4000);
+        assert_eq!(*matching[0].get(Property(3)), 1..=4000);
+    }
+
+    #[test]
+    fn applies_inclusive_conditions_correctly() {
+        let (matching, remaining) = split_x(Operator::LessOrEqual, 420);
+        assert_eq!(x_ranges(&matching), [1..=420]);
+        assert_eq!(x_ranges(&remaining), [421..=4000]);
+
+        let (matching, remaining) = split_x(Operator::GreaterOrEqual, 420);
+        assert_eq!(x_ranges(&matching), [420..=4000]);
+        assert_eq!(x_ranges(&remaining), [1..=419]);
+    }
+
+    #[test]
+    fn applies_equality_conditions_correctly() {
+        let (matching, remaining) = split_x(Operator::Equal, 420);
+        assert_eq!(x_ranges(&matching), [420..=420]);
+        assert_eq!(x_ranges(&remaining), [1..=419, 421..=4000]);
+
+        let (matching, remaining) = split_x(Operator::NotEqual, 420);
+        assert_eq!(x_ranges(&matching), [1..=419, 421..=4000]);
+        assert_eq!(x_ranges(&remaining), [420..=420]);
+    }
+
+    #[test]
+    fn drops_empty_pieces_at_the_edges() {
+        let (matching, remaining) = split_x(Operator::NotEqual, 1);
+        assert_eq!(x_ranges(&matching), [2..=4000]);
+        assert_eq!(x_ranges(&remaining), [1..=1]);
+
+        let (matching, remaining) = split_x(Operator::Less, 0);
+        assert!(matching.is_empty());
+        assert_eq!(x_ranges(&remaining), [1..=4000]);
+
+        let (matching, remaining) = split_x(Operator::Equal, 5000);
+        assert!(matching.is_empty());
+        assert_eq!(x_ranges(&remaining), [1..=4000]);
+    }
+
+    #[test]
+    fn calculates_combinations_correctly() {
+        let range = RatingBox::from_range(1..=2, 4); // 2 ^ 4 combinations
+
+        assert_eq!(range.combinations_count(), 16);
+    }
+}
@@ -1,6 +1,6 @@
-use std::str::FromStr;
-use crate::{Condition, Destination};
+use crate::{Condition, Destination, ParseError};
 use crate::part::Part;
+use crate::schema::Schema;
 
 #[derive(Debug, Clone)]
 pub struct Rule {
@@ -36,24 +36,22 @@ impl Rule {
     }
 
     pub fn check_condition_for_part(&self, part: &Part) -> bool {
-        if self.condition.is_none() {
-            // println!("Sending directly to: {:?}", self.destination);
-            return true;
+        self.condition.is_none_or(|condition| condition.check(part))
+    }
+
+    pub fn with_destination(&self, destination: Destination) -> Self {
+        Self {
+            condition: self.condition,
+            destination,
         }
-        let Condition(property, operation, value) = self.condition.unwrap();
-        let result = part.get(property).cmp(&value);
-        // println!("{:?} {:?} {:?} = {:?}", property, operation, value, result);
-        result == operation
     }
 }
 
-impl FromStr for Rule {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Rule {
+    pub fn parse(s: &str, schema: &mut Schema) -> Result<Self, ParseError> {
         if s.contains(':') {
             let mut split = s.split(':');
-            let condition = split.next().unwrap().parse::<Condition>()?;
+            let condition = Condition::parse(split.next().unwrap(), schema)?;
             let destination = Destination::from(split.next().unwrap());
             Ok(Self::with_condition(condition, destination))
         } else {
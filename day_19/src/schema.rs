@@ -0,0 +1,82 @@
+use crate::{ParseError, Property};
+
+/// The named ratings parts carry. A declared schema rejects names it doesn't know, while an
+/// inferred one appends them in the order they first show up in the input.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Schema {
+    names: Vec<String>,
+    declared: bool,
+}
+
+impl Schema {
+    pub fn declared<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+            declared: true,
+        }
+    }
+
+    pub fn inferred() -> Self {
+        Self {
+            names: Vec::new(),
+            declared: false,
+        }
+    }
+
+    /// The puzzle's `x`, `m`, `a` and `s` ratings, in that order.
+    pub fn xmas() -> Self {
+        Self::declared(["x", "m", "a", "s"])
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn name(&self, property: Property) -> &str {
+        &self.names[property.0]
+    }
+
+    pub fn property(&self, name: &str) -> Option<Property> {
+        self.names.iter().position(|n| n == name).map(Property)
+    }
+
+    pub fn resolve(&mut self, name: &str) -> Result<Property, ParseError> {
+        if let Some(property) = self.property(name) {
+            return Ok(property);
+        }
+        if self.declared {
+            return Err(ParseError::UnknownProperty(name.to_owned()));
+        }
+        self.names.push(name.to_owned());
+        Ok(Property(self.names.len() - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParseError, Property};
+    use crate::schema::Schema;
+
+    #[test]
+    fn declared_schema_rejects_unknown_names() {
+        let mut schema = Schema::xmas();
+
+        assert_eq!(schema.resolve("a"), Ok(Property(2)));
+        assert_eq!(schema.resolve("weight"), Err(ParseError::UnknownProperty("weight".to_owned())));
+        assert_eq!(schema.len(), 4);
+    }
+
+    #[test]
+    fn inferred_schema_appends_new_names() {
+        let mut schema = Schema::inferred();
+
+        assert_eq!(schema.resolve("weight"), Ok(Property(0)));
+        assert_eq!(schema.resolve("size"), Ok(Property(1)));
+        assert_eq!(schema.resolve("weight"), Ok(Property(0)));
+        assert_eq!(schema.name(Property(1)), "size");
+    }
+}
@@ -1,66 +1,291 @@
 use std::collections::HashMap;
 use std::io::{BufRead, Lines};
 use std::ops::RangeInclusive;
-use std::str::FromStr;
-use crate::{Destination, Workflow};
+use crate::{Destination, ParseError, STARTING_POINT, Workflow};
+use crate::decision_tree::CompileError;
 use crate::part::Part;
-use crate::rule::Rule;
-use crate::xmas_range::XMASRange;
+use crate::schema::Schema;
+use crate::rating_box::RatingBox;
 
-pub struct WorkflowMap(HashMap<String, Workflow>);
+pub struct WorkflowMap {
+    workflows: HashMap<String, Workflow>,
+    schema: Schema,
+}
 
 impl WorkflowMap {
-    pub fn from_lines<B: BufRead>(lines: &mut Lines<B>) -> Self {
+    pub fn from_lines<B: BufRead>(lines: &mut Lines<B>, mut schema: Schema) -> Self {
         let mut map = HashMap::default();
         for line_result in lines {
             let line = line_result.unwrap();
             if line.is_empty() {
                 break;
             }
-            let workflow = Workflow::from_str(&line).unwrap();
+            let workflow = Workflow::parse(&line, &mut schema).unwrap();
             map.insert(workflow.id.to_owned(), workflow);
         }
-        Self(map)
+        Self {
+            workflows: map,
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Parses a part, adding any new property names to an inferred schema.
+    pub fn parse_part(&mut self, s: &str) -> Result<Part, ParseError> {
+        Part::parse(s, &mut self.schema)
     }
 
     pub fn check_accepted(&self, part: &Part) -> bool {
         let mut cur_workflow = self.starting_workflow();
         loop {
             // println!("{}", cur_workflow.id);
-            let next_destination = cur_workflow.get_destination_for_part(&part)
+            let next_destination = cur_workflow.get_destination_for_part(part)
                 .expect("No destination found!");
             match next_destination {
                 Destination::Accept => return true,
                 Destination::Reject => return false,
                 Destination::SendTo(id) => {
-                    cur_workflow = self.0.get(id).expect("No next workflow found!");
+                    cur_workflow = self.workflows.get(id).expect("No next workflow found!");
                 }
             }
         }
     }
 
     pub fn starting_workflow(&self) -> &Workflow {
-        self.0.get("in").expect("No \"in\" workflow.")
+        self.workflows.get(STARTING_POINT).expect("No \"in\" workflow.")
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Workflow> {
+        self.workflows.get(id)
     }
 
-    pub fn calculate_combinations(&self, range: RangeInclusive<usize>) -> usize {
-        let ranges = XMASRange::from_range(range);
-        self.calculate_combinations_cached(ranges, self.starting_workflow())
+    pub fn workflows(&self) -> impl Iterator<Item = &Workflow> {
+        self.workflows.values()
     }
 
-    pub fn calculate_combinations_cached(
-        &self,
-        ranges: XMASRange,
-        from_workflow: &Workflow,
-    ) -> usize {
-        let mut cur_ranges = ranges.clone();
+    /// Returns an equivalent map where conditional rules that send to the same place as the
+    /// fallback (like `a<5:A,A`) are dropped, and workflows that end up with a single destination
+    /// are inlined into the rules that referenced them.
+    pub fn simplified(&self) -> Self {
+        let mut map = self.workflows.clone();
+        loop {
+            for workflow in map.values_mut() {
+                while let [.., previous, last] = workflow.rules.as_slice() {
+                    if last.condition().is_some() || previous.destination() != last.destination() {
+                        break;
+                    }
+                    let fallback_index = workflow.rules.len() - 2;
+                    workflow.rules.remove(fallback_index);
+                }
+            }
+
+            let inlined = map.values()
+                .filter(|w| w.id != STARTING_POINT)
+                .find_map(|w| match w.rules.as_slice() {
+                    [rule] if rule.condition().is_none() => Some((w.id.clone(), rule.destination().clone())),
+                    _ => None,
+                });
+            let Some((id, destination)) = inlined else {
+                return Self {
+                    workflows: map,
+                    schema: self.schema.clone(),
+                };
+            };
+
+            map.remove(&id);
+            let target = Destination::SendTo(id);
+            for workflow in map.values_mut() {
+                for rule in workflow.rules.iter_mut() {
+                    if *rule.destination() == target {
+                        *rule = rule.with_destination(destination.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn calculate_combinations(&self, range: RangeInclusive<usize>) -> Result<usize, CompileError> {
+        let ranges = RatingBox::from_range(range, self.schema.len());
+        self.combinations_from(ranges, self.starting_workflow())
+    }
+
+    fn combinations_from(&self, ranges: RatingBox, from_workflow: &Workflow) -> Result<usize, CompileError> {
+        let mut cur_ranges = vec![ranges];
         let mut accepted = 0;
         for rule in from_workflow.rules.iter() {
-            if let Some(condition) = rule.condition() {
-                // TODO
-                continue;
+            if RatingBox::total_combinations(&cur_ranges) == 0 {
+                return Ok(accepted);
+            }
+            match rule.condition() {
+                Some(condition) => {
+                    let (matching, remaining) = RatingBox::split_all(&cur_ranges, condition);
+                    for r in matching {
+                        accepted += self.combinations_for_destination(r, rule.destination())?;
+                    }
+                    cur_ranges = remaining;
+                }
+                None => {
+                    for r in cur_ranges {
+                        accepted += self.combinations_for_destination(r, rule.destination())?;
+                    }
+                    return Ok(accepted);
+                }
+            }
+        }
+        if RatingBox::total_combinations(&cur_ranges) == 0 {
+            return Ok(accepted);
+        }
+        Err(CompileError::NoFallback(from_workflow.id.clone()))
+    }
+
+    fn combinations_for_destination(&self, ranges: RatingBox, destination: &Destination) -> Result<usize, CompileError> {
+        if ranges.combinations_count() == 0 {
+            return Ok(0);
+        }
+        match destination {
+            Destination::Accept => Ok(ranges.combinations_count()),
+            Destination::Reject => Ok(0),
+            Destination::SendTo(id) => {
+                let workflow = self.workflows.get(id)
+                    .ok_or_else(|| CompileError::UnknownWorkflow(id.clone()))?;
+                self.combinations_from(ranges, workflow)
             }
         }
-        panic!("Reached end of loop!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use crate::ParseError;
+    use crate::decision_tree::CompileError;
+    use crate::part::Part;
+    use crate::schema::Schema;
+    use crate::workflow_map::WorkflowMap;
+
+    const EXAMPLE: &str = "\
+px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+";
+
+    #[test]
+    fn calculates_example_combinations() {
+        let workflow = WorkflowMap::from_lines(&mut EXAMPLE.as_bytes().lines(), Schema::xmas());
+
+        assert_eq!(workflow.calculate_combinations(1..=4000).unwrap(), 167409079868000);
+    }
+
+    #[test]
+    fn single_rule_workflows_split_ranges() {
+        let input = "in{x<3:A,m>1:R,A}\n";
+        let workflow = WorkflowMap::from_lines(&mut input.as_bytes().lines(), Schema::xmas());
+
+        // x in 1..=2 is accepted outright, the rest only when m == 1.
+        assert_eq!(workflow.calculate_combinations(1..=4).unwrap(), 2 * 4 * 4 * 4 + 2 * 4 * 4);
+    }
+
+    #[test]
+    fn counts_inclusive_and_equality_conditions() {
+        let input = "in{x<=2:A,m!=1:nx,R}\nnx{a=3:R,s>=4:A,R}\n";
+        let workflow = WorkflowMap::from_lines(&mut input.as_bytes().lines(), Schema::xmas());
+
+        let brute_force = (1..=4).flat_map(|x| (1..=4).flat_map(move |m| (1..=4)
+            .flat_map(move |a| (1..=4).map(move |s| Part::new([x, m, a, s])))))
+            .filter(|part| workflow.check_accepted(part))
+            .count();
+        assert_eq!(brute_force, 2 * 64 + 2 * 3 * 3);
+        assert_eq!(workflow.calculate_combinations(1..=4).unwrap(), brute_force);
+    }
+
+    #[test]
+    fn infers_properties_from_the_input() {
+        let input = "in{weight>10:heavy,A}\nheavy{size<=3:A,R}\n\n{weight=12,size=2,colour=5}\n{weight=12,size=4,colour=5}\n";
+        let mut lines = input.as_bytes().lines();
+        let mut workflow = WorkflowMap::from_lines(&mut lines, Schema::inferred());
+        let parts = lines.map(|line| workflow.parse_part(&line.unwrap()).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(workflow.schema().names(), ["weight", "size", "colour"]);
+        assert!(workflow.check_accepted(&parts[0]));
+        assert!(!workflow.check_accepted(&parts[1]));
+        // weight <= 10, or weight > 10 with size <= 3; colour is free either way.
+        assert_eq!(workflow.calculate_combinations(1..=20).unwrap(), (10 * 20 + 10 * 3) * 20);
+    }
+
+    #[test]
+    fn parts_require_every_rating() {
+        let mut workflow = WorkflowMap::from_lines(&mut "in{A}\n".as_bytes().lines(), Schema::xmas());
+
+        assert!(workflow.parse_part("{x=1,m=2,a=3,s=4}").is_ok());
+        assert_eq!(workflow.parse_part("{x=1,m=2,s=4}").unwrap_err(), ParseError::MissingProperty("a".to_owned()));
+
+        let mut workflow = WorkflowMap::from_lines(&mut "in{weight>10:R,A}\n".as_bytes().lines(), Schema::inferred());
+
+        assert_eq!(workflow.parse_part("{size=3}").unwrap_err(), ParseError::MissingProperty("weight".to_owned()));
+        assert!(workflow.parse_part("{size=3,weight=4}").is_ok());
+        assert_eq!(workflow.parse_part("{weight=4}").unwrap_err(), ParseError::MissingProperty("size".to_owned()));
+    }
+
+    #[test]
+    fn empty_schema_has_a_single_combination() {
+        let workflow = WorkflowMap::from_lines(&mut "in{A}\n".as_bytes().lines(), Schema::inferred());
+
+        assert_eq!(workflow.schema().len(), 0);
+        assert_eq!(workflow.calculate_combinations(1..=4000).unwrap(), 1);
+    }
+
+    #[test]
+    fn reports_incomplete_workflows() {
+        let read = |input: &str| WorkflowMap::from_lines(&mut input.as_bytes().lines(), Schema::xmas());
+
+        assert_eq!(read("in{x<10:A}\n").calculate_combinations(1..=4000), Err(CompileError::NoFallback("in".to_owned())));
+        assert_eq!(read("in{x<10:A}\n").calculate_combinations(1..=9), Ok(9 * 9 * 9 * 9));
+        assert_eq!(read("in{x<10:gd,A}\n").calculate_combinations(1..=4000), Err(CompileError::UnknownWorkflow("gd".to_owned())));
+    }
+
+    #[test]
+    fn simplifies_redundant_rules() {
+        let input = "in{x<5:gd,m>10:A,A}\ngd{a>3333:R,R}\n";
+        let workflow = WorkflowMap::from_lines(&mut input.as_bytes().lines(), Schema::xmas()).simplified();
+
+        assert!(workflow.get("gd").is_none());
+        let rules = &workflow.starting_workflow().rules;
+        assert_eq!(rules.len(), 2);
+        assert!(!workflow.check_accepted(&Part::new([4, 11, 1, 1])));
+        assert!(workflow.check_accepted(&Part::new([5, 1, 1, 1])));
+    }
+
+    #[test]
+    fn keeps_rules_before_a_conditional_last_rule() {
+        // The last rule is no fallback, so dropping `x<5:A` would leave parts with x < 5 unmatched
+        let input = "in{x<5:A,x>=5:A}\n";
+        let workflow = WorkflowMap::from_lines(&mut input.as_bytes().lines(), Schema::xmas());
+        let simplified = workflow.simplified();
+
+        assert_eq!(simplified.starting_workflow().rules.len(), 2);
+        assert_eq!(simplified.calculate_combinations(1..=20), Ok(20 * 20 * 20 * 20));
+    }
+
+    #[test]
+    fn simplified_example_keeps_combinations() {
+        let workflow = WorkflowMap::from_lines(&mut EXAMPLE.as_bytes().lines(), Schema::xmas());
+        let simplified = workflow.simplified();
+
+        assert!(simplified.get("gd").is_none());
+        assert!(simplified.get("lnx").is_none());
+        assert_eq!(simplified.calculate_combinations(1..=4000).unwrap(), workflow.calculate_combinations(1..=4000).unwrap());
     }
 }
@@ -1,23 +1,19 @@
-use std::fmt::{Display, Formatter};
+use std::collections::VecDeque;
+use crate::module::ModuleId;
 use crate::Pulse;
 
-pub type ActionQueue = Vec<Action>;
+/// Pending pulses, each with the conjunction memory bit it updates when delivered.
+pub type ActionQueue = VecDeque<(Action, usize)>;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Action {
-    pub from: String,
+    pub from: ModuleId,
     pub pulse: Pulse,
-    pub to: String,
+    pub to: ModuleId,
 }
 
 impl Action {
-    pub fn new(from: &str, pulse: Pulse, to: &str) -> Self {
-        Self { from: from.to_owned(), pulse, to: to.to_owned() }
-    }
-}
-
-impl Display for Action {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} -{}-> {}", self.from, self.pulse, self.to)
+    pub fn new(from: ModuleId, pulse: Pulse, to: ModuleId) -> Self {
+        Self { from, pulse, to }
     }
 }
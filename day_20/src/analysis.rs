@@ -0,0 +1,219 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use crate::module::{ModuleId, ModuleInfo};
+use crate::modules_network::ModulesNetwork;
+
+/// A chain of flip-flops counting button presses, reset by a conjunction (the hub) once the
+/// flip-flops wired into it are all on.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BinaryCounter {
+    /// Flip-flops from the least significant bit up.
+    pub bits: Vec<String>,
+    pub hub: String,
+    /// The number formed by the bits that feed the hub.
+    pub value: usize,
+}
+
+/// Modules reachable from one of the broadcaster outputs without going through the final
+/// conjunction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Subgraph {
+    pub entry: String,
+    pub modules: Vec<String>,
+    /// The modules that send pulses to the final conjunction.
+    pub exits: Vec<String>,
+    pub counter: Option<BinaryCounter>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AnalysisError {
+    NoFinalConjunction(String),
+    SharedModule { module: String, entries: (String, String) },
+}
+
+impl Display for AnalysisError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoFinalConjunction(target) =>
+                write!(f, "{target} is not fed by a single conjunction"),
+            Self::SharedModule { module, entries: (first, second) } =>
+                write!(f, "{module} is reachable from both {first} and {second}"),
+        }
+    }
+}
+
+/// Splits the network between the broadcaster and the conjunction feeding `target` into
+/// independent subgraphs, reading each one as a binary counter where possible.
+pub fn find_subgraphs(network: &ModulesNetwork, target: &str) -> Result<Vec<Subgraph>, AnalysisError> {
+    let no_final_conjunction = || AnalysisError::NoFinalConjunction(target.to_owned());
+    let target = network.id_of(target).ok_or_else(no_final_conjunction)?;
+    let last = match network.module(target).inputs() {
+        &[last] if network.module(last).info() == ModuleInfo::Conjunction => last,
+        _ => return Err(no_final_conjunction()),
+    };
+    let broadcaster = network.broadcaster();
+
+    let mut owners: HashMap<ModuleId, ModuleId> = HashMap::new();
+    let mut subgraphs = Vec::new();
+    for &entry in network.module(broadcaster).outputs() {
+        let modules = reachable_from(network, entry, &[broadcaster, last]);
+        for &module in modules.iter() {
+            if let Some(&owner) = owners.get(&module) {
+                let name = |id| network.module(id).name().to_owned();
+                return Err(AnalysisError::SharedModule {
+                    module: name(module),
+                    entries: (name(owner), name(entry)),
+                });
+            }
+            owners.insert(module, entry);
+        }
+
+        let mut exits = modules.iter()
+            .filter(|&&m| network.module(m).outputs().contains(&last))
+            .map(|&m| network.module(m).name().to_owned())
+            .collect::<Vec<_>>();
+        exits.sort();
+        let mut names = modules.iter()
+            .map(|&m| network.module(m).name().to_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        subgraphs.push(Subgraph {
+            entry: network.module(entry).name().to_owned(),
+            modules: names,
+            exits,
+            counter: read_counter(network, entry),
+        });
+    }
+    Ok(subgraphs)
+}
+
+fn reachable_from(network: &ModulesNetwork, entry: ModuleId, excluded: &[ModuleId]) -> BTreeSet<ModuleId> {
+    let mut reached = BTreeSet::from([entry]);
+    let mut queue = VecDeque::from([entry]);
+    while let Some(id) = queue.pop_front() {
+        for &output in network.module(id).outputs() {
+            if !excluded.contains(&output) && reached.insert(output) {
+                queue.push_back(output);
+            }
+        }
+    }
+    reached
+}
+
+/// Follows the flip-flop chain starting at `entry`, expecting every bit to be wired to or from
+/// one shared conjunction.
+fn read_counter(network: &ModulesNetwork, entry: ModuleId) -> Option<BinaryCounter> {
+    let is_flip_flop = |id: ModuleId| network.module(id).info() == ModuleInfo::FlipFlop;
+    let mut bits = vec![];
+    let mut current = Some(entry).filter(|&id| is_flip_flop(id));
+    while let Some(bit) = current {
+        if bits.contains(&bit) {
+            return None;
+        }
+        bits.push(bit);
+        current = network.module(bit).outputs().iter().copied().find(|&o| is_flip_flop(o));
+    }
+
+    let is_conjunction = |id: &&ModuleId| network.module(**id).info() == ModuleInfo::Conjunction;
+    let hub = *network.module(*bits.first()?).outputs().iter()
+        .chain(network.module(bits[0]).inputs())
+        .find(is_conjunction)?;
+    let mut value = 0;
+    for (i, &bit) in bits.iter().enumerate() {
+        let module = network.module(bit);
+        if module.outputs().contains(&hub) {
+            value |= 1 << i;
+        } else if !module.inputs().contains(&hub) {
+            return None;
+        }
+    }
+    Some(BinaryCounter {
+        bits: bits.iter().map(|&b| network.module(b).name().to_owned()).collect(),
+        hub: network.module(hub).name().to_owned(),
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{find_subgraphs, AnalysisError};
+    use crate::cycle_solver::presses_until_low_pulse;
+    use crate::modules_network::ModulesNetwork;
+
+    /// Builds a counter the way puzzle inputs do: bits set in `value` feed the hub, and the hub
+    /// sends a pulse to the rest of the bits and to the first one to roll the count over.
+    fn counter(name: &str, value: usize, bit_count: usize) -> Vec<String> {
+        let bit = |i: usize| format!("{name}{i}");
+        let hub = format!("{name}hub");
+        let mut lines = vec![];
+        let mut hub_outputs = vec![format!("{name}out")];
+        for i in 0..bit_count {
+            let mut outputs = vec![];
+            if i + 1 < bit_count {
+                outputs.push(bit(i + 1));
+            }
+            if value & (1 << i) != 0 {
+                outputs.push(hub.clone());
+            }
+            if value & (1 << i) == 0 || i == 0 {
+                hub_outputs.push(bit(i));
+            }
+            lines.push(format!("%{} -> {}", bit(i), outputs.join(", ")));
+        }
+        lines.push(format!("&{hub} -> {}", hub_outputs.join(", ")));
+        lines.push(format!("&{name}out -> last"));
+        lines
+    }
+
+    fn counters_network(counters: &[(&str, usize)]) -> String {
+        let entries = counters.iter().map(|(name, _)| format!("{name}0")).collect::<Vec<_>>();
+        let mut lines = vec![format!("broadcaster -> {}", entries.join(", "))];
+        for &(name, value) in counters {
+            lines.extend(counter(name, value, 4));
+        }
+        lines.push("&last -> rx".to_owned());
+        lines.join("\n")
+    }
+
+    #[test]
+    fn reads_counter_values() {
+        let input = counters_network(&[("a", 0b1011), ("b", 0b1101)]);
+        let network = ModulesNetwork::from_reader(input.as_bytes());
+
+        let subgraphs = find_subgraphs(&network, "rx").unwrap();
+
+        let counters = subgraphs.iter().map(|s| s.counter.clone().unwrap()).collect::<Vec<_>>();
+        assert_eq!(counters[0].bits, ["a0", "a1", "a2", "a3"]);
+        assert_eq!(counters[0].hub, "ahub");
+        assert_eq!(counters.iter().map(|c| c.value).collect::<Vec<_>>(), [11, 13]);
+        assert_eq!(subgraphs[0].exits, ["aout"]);
+        assert_eq!(subgraphs[1].modules, ["b0", "b1", "b2", "b3", "bhub", "bout"]);
+    }
+
+    #[test]
+    fn counter_values_match_simulated_cycles() {
+        let input = counters_network(&[("a", 0b1011), ("b", 0b1101), ("c", 0b1111)]);
+        let mut network = ModulesNetwork::from_reader(input.as_bytes());
+
+        let values = find_subgraphs(&network, "rx").unwrap().into_iter()
+            .map(|s| s.counter.unwrap().value)
+            .collect::<Vec<_>>();
+        let (presses, cycles) = presses_until_low_pulse(&mut network, "rx", 1000).unwrap();
+
+        assert_eq!(cycles.iter().map(|c| c.period).collect::<Vec<_>>(), values);
+        assert_eq!(presses, 11 * 13 * 15);
+    }
+
+    #[test]
+    fn rejects_shared_modules() {
+        let input = "broadcaster -> a, b\n%a -> c\n%b -> c\n&c -> last\n&last -> rx";
+        let network = ModulesNetwork::from_reader(input.as_bytes());
+
+        let result = find_subgraphs(&network, "rx");
+
+        assert_eq!(result, Err(AnalysisError::SharedModule {
+            module: "c".to_owned(),
+            entries: ("a".to_owned(), "b".to_owned()),
+        }));
+    }
+}
@@ -0,0 +1,34 @@
+/// A fixed size set of bits, used to pack module state so it can be compared and hashed cheaply.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) {
+        let word = &mut self.words[index / 64];
+        if value {
+            *word |= 1 << (index % 64);
+        } else {
+            *word &= !(1 << (index % 64));
+        }
+    }
+
+    /// Flips a bit, returning its new value.
+    pub fn toggle(&mut self, index: usize) -> bool {
+        self.words[index / 64] ^= 1 << (index % 64);
+        self.get(index)
+    }
+}
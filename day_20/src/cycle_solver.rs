@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use num::integer::lcm;
+use crate::action_queue::Action;
+use crate::module::{ModuleId, ModuleInfo};
+use crate::modules_network::ModulesNetwork;
+use crate::Pulse;
+
+/// How many times every input of the feeding conjunction has to send a high pulse before its
+/// period is trusted.
+const FIRINGS_TO_VERIFY: usize = 3;
+
+/// Presses on which each input sent a high pulse.
+type Firings = HashMap<ModuleId, Vec<usize>>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InputCycle {
+    pub input: String,
+    pub period: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CycleError {
+    NoFeeder(String),
+    MultipleFeeders(Vec<String>),
+    FeederNotConjunction(String),
+    NotEnoughFirings { input: String, presses: Vec<usize> },
+    MultipleHighPulses { input: String, press: usize },
+    OffsetCycle { input: String, first: usize, period: usize },
+    IrregularPeriod { input: String, presses: Vec<usize> },
+    Contradicted { observed: usize, predicted: usize },
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoFeeder(target) => write!(f, "no module sends pulses to {target}"),
+            Self::MultipleFeeders(feeders) => write!(f, "expected a single feeder, found {}", feeders.join(", ")),
+            Self::FeederNotConjunction(id) => write!(f, "feeder {id} is not a conjunction"),
+            Self::NotEnoughFirings { input, presses } =>
+                write!(f, "{input} only sent high pulses on presses {presses:?}"),
+            Self::MultipleHighPulses { input, press } =>
+                write!(f, "{input} sent more than one high pulse on press {press}"),
+            Self::OffsetCycle { input, first, period } =>
+                write!(f, "{input} first fires on press {first} but repeats every {period}"),
+            Self::IrregularPeriod { input, presses } =>
+                write!(f, "{input} fires on presses {presses:?}, which is not periodic"),
+            Self::Contradicted { observed, predicted } =>
+                write!(f, "predicted press {predicted}, but a low pulse was seen on press {observed}"),
+        }
+    }
+}
+
+/// Finds the first button press on which `target` receives a low pulse, assuming it is fed by a
+/// single conjunction whose inputs each send one high pulse every fixed number of presses. Those
+/// assumptions are checked while pressing `network` from its initial state, and whatever state
+/// it was in is restored afterwards.
+pub fn presses_until_low_pulse(
+    network: &mut ModulesNetwork,
+    target: &str,
+    max_presses: usize,
+) -> Result<(usize, Vec<InputCycle>), CycleError> {
+    let target_id = network.id_of(target).ok_or_else(|| CycleError::NoFeeder(target.to_owned()))?;
+    let feeder = match network.module(target_id).inputs() {
+        [] => return Err(CycleError::NoFeeder(target.to_owned())),
+        &[feeder] => feeder,
+        feeders => {
+            let mut names = feeders.iter().map(|&f| network.module(f).name().to_owned()).collect::<Vec<_>>();
+            names.sort();
+            return Err(CycleError::MultipleFeeders(names));
+        }
+    };
+    let feeder_module = network.module(feeder);
+    if feeder_module.info() != ModuleInfo::Conjunction {
+        return Err(CycleError::FeederNotConjunction(feeder_module.name().to_owned()));
+    }
+    let mut inputs = feeder_module.inputs().to_vec();
+    inputs.sort_by_key(|&input| network.module(input).name());
+
+    let saved_state = network.snapshot();
+    network.reset();
+    let firings = record_firings(network, target_id, feeder, &inputs, max_presses);
+    network.restore(&saved_state);
+    let (mut firings, first_low) = firings?;
+
+    let mut cycles = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let presses = firings.remove(&input).unwrap();
+        cycles.push(verify_cycle(network.module(input).name().to_owned(), presses)?);
+    }
+    let presses = cycles.iter().map(|c| c.period).fold(1, lcm);
+    match first_low {
+        Some(observed) if observed != presses => Err(CycleError::Contradicted { observed, predicted: presses }),
+        _ => Ok((presses, cycles)),
+    }
+}
+
+/// Presses the button until every input has fired enough times, returning the presses each one
+/// sent a high pulse to `feeder` on and the first press `target` got a low pulse on, if any.
+fn record_firings(
+    network: &mut ModulesNetwork,
+    target: ModuleId,
+    feeder: ModuleId,
+    inputs: &[ModuleId],
+    max_presses: usize,
+) -> Result<(Firings, Option<usize>), CycleError> {
+    let mut firings: Firings = inputs.iter().map(|&i| (i, vec![])).collect();
+    let mut press_highs: HashMap<ModuleId, usize> = HashMap::new();
+    let mut first_low = None;
+    for press in 1..=max_presses {
+        let mut target_got_low = false;
+        network.start_process_observed(&mut |action: &Action| {
+            if action.to == target && action.pulse == Pulse::Low {
+                target_got_low = true;
+            }
+            if action.to == feeder && action.pulse == Pulse::High {
+                *press_highs.entry(action.from).or_default() += 1;
+            }
+        });
+        if target_got_low && first_low.is_none() {
+            first_low = Some(press);
+        }
+
+        for (input, count) in press_highs.drain() {
+            if count > 1 {
+                let input = network.module(input).name().to_owned();
+                return Err(CycleError::MultipleHighPulses { input, press });
+            }
+            firings.get_mut(&input).unwrap().push(press);
+        }
+        if firings.values().all(|presses| presses.len() >= FIRINGS_TO_VERIFY) {
+            break;
+        }
+    }
+    Ok((firings, first_low))
+}
+
+fn verify_cycle(input: String, presses: Vec<usize>) -> Result<InputCycle, CycleError> {
+    if presses.len() < FIRINGS_TO_VERIFY {
+        return Err(CycleError::NotEnoughFirings { input, presses });
+    }
+    let first = presses[0];
+    let period = presses[1] - presses[0];
+    if presses.windows(2).any(|w| w[1] - w[0] != period) {
+        return Err(CycleError::IrregularPeriod { input, presses });
+    }
+    if first != period {
+        return Err(CycleError::OffsetCycle { input, first, period });
+    }
+    Ok(InputCycle { input, period })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action_queue::Action;
+    use crate::cycle_solver::{presses_until_low_pulse, CycleError, InputCycle};
+    use crate::modules_network::ModulesNetwork;
+    use crate::Pulse;
+
+    // `i1` inverts a flip-flop that turns off every 2 presses and `i2` one that does so every 4.
+    const COUNTERS: &str = "\
+broadcaster -> f1
+%f1 -> i1, f2
+%f2 -> i2
+&i1 -> fd
+&i2 -> fd
+&fd -> rx";
+
+    fn brute_force(input: &str, target: &str) -> usize {
+        let mut network = ModulesNetwork::from_reader(input.as_bytes());
+        let target = network.id_of(target).unwrap();
+        (1..).find(|_| {
+            let mut found = false;
+            network.start_process_observed(&mut |action: &Action| {
+                found |= action.to == target && action.pulse == Pulse::Low;
+            });
+            found
+        }).unwrap()
+    }
+
+    #[test]
+    fn combines_input_periods() {
+        let mut network = ModulesNetwork::from_reader(COUNTERS.as_bytes());
+
+        let (presses, cycles) = presses_until_low_pulse(&mut network, "rx", 100).unwrap();
+
+        assert_eq!(presses, brute_force(COUNTERS, "rx"));
+        assert_eq!(cycles, [
+            InputCycle { input: "i1".to_owned(), period: 2 },
+            InputCycle { input: "i2".to_owned(), period: 4 },
+        ]);
+    }
+
+    #[test]
+    fn rejects_inputs_that_never_fire() {
+        let input = "broadcaster -> f1\n%f1 -> i1\n&i1 -> fd\n&off -> fd\n&fd -> rx";
+        let mut network = ModulesNetwork::from_reader(input.as_bytes());
+
+        let result = presses_until_low_pulse(&mut network, "rx", 20);
+
+        assert_eq!(result, Err(CycleError::NotEnoughFirings { input: "off".to_owned(), presses: vec![] }));
+    }
+
+    #[test]
+    fn rejects_feeders_that_are_not_conjunctions() {
+        let mut network = ModulesNetwork::from_reader("broadcaster -> f1\n%f1 -> rx".as_bytes());
+
+        let result = presses_until_low_pulse(&mut network, "rx", 20);
+
+        assert_eq!(result, Err(CycleError::FeederNotConjunction("f1".to_owned())));
+    }
+}
@@ -1,24 +1,119 @@
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter};
+use std::str::FromStr;
 use crate::modules_network::ModulesNetwork;
+use crate::trace::Trace;
 
 mod module;
 mod action_queue;
 mod modules_network;
+mod cycle_solver;
+mod bitset;
+mod analysis;
+mod trace;
+
+enum Mode {
+    Solve,
+    Dot,
+    Trace { path: String, presses: usize },
+    Diff { expected: String, actual: String },
+}
+
+impl Mode {
+    fn from_args() -> Self {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        let value_after = |flag: &str| {
+            args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).cloned()
+        };
+        if args.iter().any(|arg| arg == "--dot") {
+            Mode::Dot
+        } else if let Some(path) = value_after("--trace") {
+            let presses = value_after("--presses")
+                .map(|n| n.parse().expect("--presses expects a number"))
+                .unwrap_or(1);
+            Mode::Trace { path, presses }
+        } else if let Some(expected) = value_after("--diff") {
+            let position = args.iter().position(|arg| arg == "--diff").unwrap();
+            let actual = args.get(position + 2).cloned().expect("--diff expects two trace files");
+            Mode::Diff { expected, actual }
+        } else {
+            Mode::Solve
+        }
+    }
+}
 
 fn main() {
-    part_1();
+    match Mode::from_args() {
+        Mode::Solve => {
+            let mut network = ModulesNetwork::from_reader(read_file());
+            part_1(&mut network);
+            network.reset();
+            part_2(&mut network);
+            print_subgraphs(&network);
+        }
+        Mode::Dot => print!("{}", ModulesNetwork::from_reader(read_file()).to_dot()),
+        Mode::Trace { path, presses } => {
+            let mut network = ModulesNetwork::from_reader(read_file());
+            let trace = Trace::record(&mut network, presses);
+            let file = File::create(&path).unwrap();
+            trace.write_to(BufWriter::new(file)).unwrap();
+            println!("Wrote {presses} press(es) to {path}");
+        }
+        Mode::Diff { expected, actual } => diff_traces(&expected, &actual),
+    }
 }
 
-fn part_1() {
-    let mut network = ModulesNetwork::from_reader(read_file());
+fn diff_traces(expected: &str, actual: &str) {
+    let read = |path: &str| {
+        let file = File::open(path).unwrap();
+        Trace::from_reader(BufReader::new(file)).unwrap_or_else(|err| panic!("{path}: {err}"))
+    };
+    let (expected, actual) = (read(expected), read(actual));
+    println!("Comparing {} press(es) against {}", actual.presses().len(), expected.presses().len());
+    let differences = expected.diff(&actual);
+    for difference in differences.iter() {
+        println!("{difference}");
+    }
+    println!("{} difference(s)", differences.len());
+}
+
+fn part_1(network: &mut ModulesNetwork) {
     // println!("{:#?}", network);
     let result = (0..1000).map(|_| network.start_process()).reduce(|acc, e| acc + e).unwrap();
     println!("{result:?}");
     println!("{}", result.product());
 }
 
+fn part_2(network: &mut ModulesNetwork) {
+    match cycle_solver::presses_until_low_pulse(network, "rx", 100_000) {
+        Ok((presses, cycles)) => {
+            for cycle in cycles {
+                println!("{} fires every {} presses", cycle.input, cycle.period);
+            }
+            println!("Presses until rx gets a low pulse: {presses}");
+        }
+        Err(err) => println!("Could not solve part 2: {err}"),
+    }
+}
+
+fn print_subgraphs(network: &ModulesNetwork) {
+    let subgraphs = match analysis::find_subgraphs(network, "rx") {
+        Ok(subgraphs) => subgraphs,
+        Err(err) => {
+            println!("Could not split the network: {err}");
+            return;
+        }
+    };
+    for subgraph in subgraphs {
+        print!("{}: {} modules", subgraph.entry, subgraph.modules.len());
+        match subgraph.counter {
+            Some(counter) => println!(", counter through {} = {} ({:b})", counter.hub, counter.value, counter.value),
+            None => println!(", not a binary counter"),
+        }
+    }
+}
+
 fn read_file() -> impl BufRead {
     let path = std::env::current_dir().unwrap().join("day_20/input.txt");
     println!("Opening file: {}", path.display());
@@ -41,6 +136,18 @@ impl Pulse {
     }
 }
 
+impl FromStr for Pulse {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Pulse::Low),
+            "high" => Ok(Pulse::High),
+            _ => Err(()),
+        }
+    }
+}
+
 impl From<bool> for Pulse {
     fn from(value: bool) -> Self {
         if value {
@@ -1,91 +1,126 @@
-use std::collections::HashSet;
-use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use crate::action_queue::{Action, ActionQueue};
-use crate::Pulse;
 
 const FLIP_FLOP_PREFIX: char = '%';
 const CONJUNCTION_PREFIX: char = '&';
 
+pub type ModuleId = usize;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ModuleInfo {
+    /// Forwards every pulse, like the broadcaster.
+    Default,
+    FlipFlop,
+    Conjunction,
+    /// Sends the first low pulse of every press.
+    Button,
+    /// Only named as an output, so it ignores whatever it receives.
+    Sink,
+}
+
 #[derive(Debug)]
 pub struct Module {
-    inputs: HashSet<String>,
-    id: String,
+    name: String,
     info: ModuleInfo,
-    outputs: Vec<String>,
+    inputs: Vec<ModuleId>,
+    outputs: Vec<ModuleId>,
+    /// For every output, the memory bit it drives when the output is a conjunction.
+    output_bits: Vec<usize>,
+    /// Index of the flip-flop bit or of the conjunction in the network state.
+    state_index: usize,
 }
 
 impl Module {
-    pub fn id(&self) -> &str {
-        &self.id
+    pub fn new(name: &str, info: ModuleInfo) -> Self {
+        Self {
+            name: name.to_owned(),
+            info,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            output_bits: Vec::new(),
+            state_index: 0,
+        }
     }
 
-    pub fn add_input(&mut self, input_id: &str) -> bool {
-        self.inputs.insert(input_id.to_owned())
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn outputs(&self) -> &[String] {
-        &self.outputs[..]
+    pub fn info(&self) -> ModuleInfo {
+        self.info
     }
 
-    pub fn process_pulse(&mut self, from: &str, pulse: Pulse, action_queue: &mut ActionQueue) {
-        let output_pulse = match &mut self.info {
-            ModuleInfo::Default => Some(pulse),
-            ModuleInfo::FlipFlop(ref mut state) if pulse == Pulse::Low => {
-                *state = state.invert();
-                Some(*state)
-            }
-            ModuleInfo::FlipFlop(_) => None,
-            ModuleInfo::Conjunction(ref mut state) => {
-                match pulse {
-                    Pulse::Low => { state.remove(from); }
-                    Pulse::High => { state.insert(from.to_owned()); }
-                }
-                Some(Pulse::from(state.len() < self.inputs.len()))
-            },
-        };
-        if let Some(output) = output_pulse {
-            for target in self.outputs.iter() {
-                let action = Action::new(&self.id, output, target);
-                action_queue.push(action);
-            }
+    pub fn set_info(&mut self, info: ModuleInfo) {
+        self.info = info;
+    }
+
+    pub fn inputs(&self) -> &[ModuleId] {
+        &self.inputs
+    }
+
+    pub fn add_input(&mut self, input: ModuleId) {
+        if !self.inputs.contains(&input) {
+            self.inputs.push(input);
         }
     }
+
+    pub fn outputs(&self) -> &[ModuleId] {
+        &self.outputs
+    }
+
+    pub fn add_output(&mut self, output: ModuleId) {
+        self.outputs.push(output);
+    }
+
+    pub fn output_bits(&self) -> &[usize] {
+        &self.output_bits
+    }
+
+    pub fn set_output_bits(&mut self, bits: Vec<usize>) {
+        self.output_bits = bits;
+    }
+
+    pub fn state_index(&self) -> usize {
+        self.state_index
+    }
+
+    pub fn set_state_index(&mut self, index: usize) {
+        self.state_index = index;
+    }
 }
 
-impl FromStr for Module {
+/// A module line of the input, before names are resolved to ids.
+#[derive(Debug)]
+pub struct ModuleDefinition {
+    pub name: String,
+    pub info: ModuleInfo,
+    pub outputs: Vec<String>,
+}
+
+impl FromStr for ModuleDefinition {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut split = s.split("->");
         let from_str = split.next().unwrap().trim();
-        let (id, info) = if from_str.starts_with(FLIP_FLOP_PREFIX) {
-            (from_str.trim_start_matches(FLIP_FLOP_PREFIX), ModuleInfo::FlipFlop(Pulse::Low))
+        let (name, info) = if from_str.starts_with(FLIP_FLOP_PREFIX) {
+            (from_str.trim_start_matches(FLIP_FLOP_PREFIX), ModuleInfo::FlipFlop)
         } else if from_str.starts_with(CONJUNCTION_PREFIX) {
-            (from_str.trim_start_matches(CONJUNCTION_PREFIX), ModuleInfo::Conjunction(HashSet::default()))
+            (from_str.trim_start_matches(CONJUNCTION_PREFIX), ModuleInfo::Conjunction)
         } else {
             (from_str, ModuleInfo::Default)
         };
 
         let targets = split
             .next()
-            .unwrap()
+            .ok_or(())?
             .split(',')
             .map(|t| t.trim().to_owned())
             .collect::<Vec<_>>();
 
         Ok(Self {
-            inputs: HashSet::default(),
-            id: id.to_owned(),
+            name: name.to_owned(),
             info,
             outputs: targets,
         })
     }
 }
-
-#[derive(Debug)]
-pub enum ModuleInfo {
-    Default,
-    FlipFlop(Pulse),
-    Conjunction(HashSet<String>),
-}
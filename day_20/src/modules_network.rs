@@ -2,54 +2,223 @@ use std::collections::HashMap;
 use std::io::BufRead;
 use std::ops::Add;
 use crate::action_queue::{Action, ActionQueue};
-use crate::module::Module;
+use crate::bitset::BitSet;
+use crate::module::{Module, ModuleDefinition, ModuleId, ModuleInfo};
 use crate::Pulse;
 
+const BUTTON: &str = "button";
+const BROADCASTER: &str = "broadcaster";
+
+/// Everything that changes while pulses are processed. Two networks built from the same input
+/// behave the same from then on if their states are equal.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct NetworkState {
+    flip_flops: BitSet,
+    /// The last pulse every conjunction got from each of its inputs, high bits set.
+    memory: BitSet,
+    /// How many memory bits are set for each conjunction.
+    high_inputs: Vec<usize>,
+}
+
 #[derive(Debug)]
 pub struct ModulesNetwork {
-    modules: HashMap<String, Module>,
+    modules: Vec<Module>,
+    ids: HashMap<String, ModuleId>,
+    button: ModuleId,
+    broadcaster: ModuleId,
+    state: NetworkState,
+    initial_state: NetworkState,
+    queue: ActionQueue,
 }
 
 impl ModulesNetwork {
     pub fn from_reader(reader: impl BufRead) -> Self {
-        let mut modules = HashMap::default();
-        let mut io = vec![];
-        for line_result in reader.lines() {
-            let new_module = line_result.unwrap().parse::<Module>().unwrap();
-            io.extend(new_module.outputs().iter().map(|s| (new_module.id().to_owned(), s.to_owned())));
-            modules.insert(new_module.id().to_owned(), new_module);
+        let definitions = reader.lines()
+            .map(|line| line.unwrap().parse::<ModuleDefinition>().unwrap())
+            .collect::<Vec<_>>();
+
+        let mut modules = Vec::<Module>::new();
+        let mut ids = HashMap::new();
+        let mut intern = |name: &str, modules: &mut Vec<Module>| -> ModuleId {
+            *ids.entry(name.to_owned()).or_insert_with(|| {
+                modules.push(Module::new(name, ModuleInfo::Sink));
+                modules.len() - 1
+            })
+        };
+        let button = intern(BUTTON, &mut modules);
+        let broadcaster = intern(BROADCASTER, &mut modules);
+        modules[button].set_info(ModuleInfo::Button);
+        modules[button].add_output(broadcaster);
+        for definition in definitions.iter() {
+            let id = intern(&definition.name, &mut modules);
+            modules[id].set_info(definition.info);
+        }
+        for definition in definitions.iter() {
+            let id = intern(&definition.name, &mut modules);
+            for output in definition.outputs.iter() {
+                let output = intern(output, &mut modules);
+                modules[id].add_output(output);
+            }
+        }
+
+        for id in 0..modules.len() {
+            for output in modules[id].outputs().to_vec() {
+                modules[output].add_input(id);
+            }
+        }
+
+        let mut flip_flop_count = 0;
+        let mut memory_offsets = vec![0; modules.len()];
+        let mut memory_len = 0;
+        let mut conjunction_count = 0;
+        for (id, module) in modules.iter_mut().enumerate() {
+            match module.info() {
+                ModuleInfo::FlipFlop => {
+                    module.set_state_index(flip_flop_count);
+                    flip_flop_count += 1;
+                }
+                ModuleInfo::Conjunction => {
+                    module.set_state_index(conjunction_count);
+                    conjunction_count += 1;
+                    memory_offsets[id] = memory_len;
+                    memory_len += module.inputs().len();
+                }
+                _ => {}
+            }
+        }
+        for id in 0..modules.len() {
+            let bits = modules[id].outputs().iter()
+                .map(|&output| {
+                    let position = modules[output].inputs().iter().position(|&i| i == id).unwrap();
+                    memory_offsets[output] + position
+                })
+                .collect();
+            modules[id].set_output_bits(bits);
+        }
+
+        let initial_state = NetworkState {
+            flip_flops: BitSet::new(flip_flop_count),
+            memory: BitSet::new(memory_len),
+            high_inputs: vec![0; conjunction_count],
+        };
+        Self {
+            modules,
+            ids,
+            button,
+            broadcaster,
+            state: initial_state.clone(),
+            initial_state,
+            queue: ActionQueue::new(),
         }
-        for (input, output) in io {
-            modules.get_mut(&output).and_then(|o| Some(o.add_input(&input)));
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<ModuleId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn module(&self, id: ModuleId) -> &Module {
+        &self.modules[id]
+    }
+
+    pub fn broadcaster(&self) -> ModuleId {
+        self.broadcaster
+    }
+
+    /// Describes the network in Graphviz's DOT language, shaping each module by its type.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph modules {\n");
+        for module in self.modules.iter() {
+            let (label, attributes) = match module.info() {
+                ModuleInfo::Button => (module.name().to_owned(), "shape=plaintext"),
+                ModuleInfo::Default => (module.name().to_owned(), "shape=doubleoctagon"),
+                ModuleInfo::FlipFlop => (format!("%{}", module.name()), "shape=box"),
+                ModuleInfo::Conjunction => (format!("&{}", module.name()), "shape=diamond, style=filled, fillcolor=lightblue"),
+                ModuleInfo::Sink => (module.name().to_owned(), "shape=doublecircle"),
+            };
+            dot.push_str(&format!("    \"{}\" [label=\"{label}\", {attributes}];\n", module.name()));
+        }
+        for module in self.modules.iter() {
+            for &output in module.outputs() {
+                dot.push_str(&format!("    \"{}\" -> \"{}\";\n", module.name(), self.modules[output].name()));
+            }
         }
-        Self { modules }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn snapshot(&self) -> NetworkState {
+        self.state.clone()
+    }
+
+    pub fn restore(&mut self, state: &NetworkState) {
+        self.state.clone_from(state);
+    }
+
+    /// Puts every flip-flop and conjunction back in the state it had after parsing.
+    pub fn reset(&mut self) {
+        self.state.clone_from(&self.initial_state);
     }
 
     pub fn start_process(&mut self) -> OutputResult {
-        let mut queue = ActionQueue::new();
-        queue.push(Action::new("button", Pulse::Low, "broadcaster"));
+        self.start_process_observed(&mut |_: &Action| {})
+    }
 
-        let mut output = OutputResult::default();
-        let mut i = 0;
-        while i < queue.len() {
-            let action = &queue[i];
-            i += 1;
+    /// Presses the button once, letting `observer` see every pulse as it is delivered.
+    pub fn start_process_observed(&mut self, observer: &mut impl PulseObserver) -> OutputResult {
+        let mut queue = std::mem::take(&mut self.queue);
+        queue.push_back((Action::new(self.button, Pulse::Low, self.broadcaster), 0));
 
+        let mut output = OutputResult::default();
+        while let Some((action, memory_bit)) = queue.pop_front() {
+            observer.on_pulse(&action);
             let pulse = action.pulse;
             match pulse {
                 Pulse::Low => { output.low_count += 1; }
                 Pulse::High => { output.high_count += 1; }
             }
 
-            if let Some(target) = self.modules.get_mut(&action.to) {
-                target.process_pulse(&action.from.to_owned(), pulse, &mut queue);
+            let target = &self.modules[action.to];
+            let output_pulse = match target.info() {
+                ModuleInfo::Default | ModuleInfo::Button => Some(pulse),
+                ModuleInfo::FlipFlop if pulse == Pulse::Low => {
+                    Some(Pulse::from(self.state.flip_flops.toggle(target.state_index())))
+                }
+                ModuleInfo::FlipFlop | ModuleInfo::Sink => None,
+                ModuleInfo::Conjunction => {
+                    let high = pulse == Pulse::High;
+                    let high_inputs = &mut self.state.high_inputs[target.state_index()];
+                    if self.state.memory.get(memory_bit) != high {
+                        self.state.memory.set(memory_bit, high);
+                        if high {
+                            *high_inputs += 1;
+                        } else {
+                            *high_inputs -= 1;
+                        }
+                    }
+                    Some(Pulse::from(*high_inputs < target.inputs().len()))
+                }
+            };
+            if let Some(output_pulse) = output_pulse {
+                for (&to, &bit) in target.outputs().iter().zip(target.output_bits()) {
+                    queue.push_back((Action::new(action.to, output_pulse, to), bit));
+                }
             }
         }
-        // queue.iter().for_each(|a| println!("{}", a));
+        self.queue = queue;
         output
     }
 }
 
+pub trait PulseObserver {
+    fn on_pulse(&mut self, action: &Action);
+}
+
+impl<F: FnMut(&Action)> PulseObserver for F {
+    fn on_pulse(&mut self, action: &Action) {
+        self(action)
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone)]
 pub struct OutputResult {
     pub low_count: usize,
@@ -72,3 +241,83 @@ impl Add for OutputResult {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::modules_network::ModulesNetwork;
+
+    const EXAMPLE_1: &str = "\
+broadcaster -> a, b, c
+%a -> b
+%b -> c
+%c -> inv
+&inv -> a";
+
+    const EXAMPLE_2: &str = "\
+broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output";
+
+    fn press_many(input: &str, presses: usize) -> usize {
+        let mut network = ModulesNetwork::from_reader(input.as_bytes());
+        (0..presses).map(|_| network.start_process()).reduce(|acc, e| acc + e).unwrap().product()
+    }
+
+    #[test]
+    fn counts_example_pulses() {
+        assert_eq!(press_many(EXAMPLE_1, 1000), 32000000);
+        assert_eq!(press_many(EXAMPLE_2, 1000), 11687500);
+    }
+
+    #[test]
+    fn exports_dot() {
+        let network = ModulesNetwork::from_reader(EXAMPLE_2.as_bytes());
+
+        let dot = network.to_dot();
+
+        assert!(dot.starts_with("digraph modules {\n"));
+        assert!(dot.contains("\"button\" [label=\"button\", shape=plaintext];"));
+        assert!(dot.contains("\"broadcaster\" [label=\"broadcaster\", shape=doubleoctagon];"));
+        assert!(dot.contains("\"a\" [label=\"%a\", shape=box];"));
+        assert!(dot.contains("\"inv\" [label=\"&inv\", shape=diamond"));
+        assert!(dot.contains("\"output\" [label=\"output\", shape=doublecircle];"));
+        assert!(dot.contains("\"a\" -> \"con\";"));
+        assert_eq!(dot.matches(" -> ").count(), 7);
+    }
+
+    #[test]
+    fn restores_snapshots() {
+        let mut network = ModulesNetwork::from_reader(EXAMPLE_2.as_bytes());
+        network.start_process();
+        let snapshot = network.snapshot();
+        let expected = (0..3).map(|_| network.start_process().product()).collect::<Vec<_>>();
+
+        network.restore(&snapshot);
+        let replayed = (0..3).map(|_| network.start_process().product()).collect::<Vec<_>>();
+        assert_eq!(replayed, expected);
+
+        network.reset();
+        assert_eq!(network.snapshot(), ModulesNetwork::from_reader(EXAMPLE_2.as_bytes()).snapshot());
+    }
+
+    #[test]
+    fn detects_state_cycles_by_hashing() {
+        let mut network = ModulesNetwork::from_reader(EXAMPLE_2.as_bytes());
+        let initial = network.snapshot();
+        let mut seen = HashMap::new();
+        let mut press = 0;
+        let first_seen = loop {
+            if let Some(first_seen) = seen.insert(network.snapshot(), press) {
+                break first_seen;
+            }
+            network.start_process();
+            press += 1;
+        };
+
+        assert_eq!((first_seen, press), (0, 4));
+        assert_eq!(network.snapshot(), initial);
+    }
+}
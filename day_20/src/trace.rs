@@ -0,0 +1,296 @@
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
+use crate::action_queue::Action;
+use crate::modules_network::ModulesNetwork;
+use crate::Pulse;
+
+/// A delivered pulse, written as in the puzzle's transcripts: `a -high-> b`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TracedPulse {
+    pub from: String,
+    pub pulse: Pulse,
+    pub to: String,
+}
+
+impl TracedPulse {
+    pub fn from_action(network: &ModulesNetwork, action: &Action) -> Self {
+        Self {
+            from: network.module(action.from).name().to_owned(),
+            pulse: action.pulse,
+            to: network.module(action.to).name().to_owned(),
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let (from, rest) = s.trim().split_once(" -")?;
+        let (pulse, to) = rest.split_once("-> ")?;
+        Some(Self {
+            from: from.to_owned(),
+            pulse: pulse.parse().ok()?,
+            to: to.to_owned(),
+        })
+    }
+}
+
+impl Display for TracedPulse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -{}-> {}", self.from, self.pulse, self.to)
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(std::io::Error),
+    NotAPulse { line: usize, text: String },
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "could not read the trace: {err}"),
+            Self::NotAPulse { line, text } => write!(f, "line {line} is not a pulse: \"{text}\""),
+        }
+    }
+}
+
+/// One position where two traces disagree. Presses and pulses are counted from 1, and a missing
+/// side means that trace ended earlier.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TraceDifference {
+    pub press: usize,
+    pub pulse: usize,
+    pub expected: Option<TracedPulse>,
+    pub actual: Option<TracedPulse>,
+}
+
+impl Display for TraceDifference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let show = |pulse: &Option<TracedPulse>| match pulse {
+            Some(pulse) => pulse.to_string(),
+            None => "nothing".to_owned(),
+        };
+        write!(f, "press {}, pulse {}: expected {}, got {}",
+               self.press, self.pulse, show(&self.expected), show(&self.actual))
+    }
+}
+
+/// Every pulse of a run of button presses, in delivery order. Written out one pulse per line
+/// with a blank line between presses.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Trace {
+    presses: Vec<Vec<TracedPulse>>,
+}
+
+impl Trace {
+    pub fn record(network: &mut ModulesNetwork, presses: usize) -> Self {
+        let mut trace = Self::default();
+        for _ in 0..presses {
+            let mut actions = vec![];
+            network.start_process_observed(&mut |action: &Action| actions.push(*action));
+            trace.presses.push(actions.iter().map(|a| TracedPulse::from_action(network, a)).collect());
+        }
+        trace
+    }
+
+    pub fn presses(&self) -> &[Vec<TracedPulse>] {
+        &self.presses
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for (i, press) in self.presses.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            for pulse in press {
+                writeln!(writer, "{pulse}")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, TraceError> {
+        let mut presses = vec![];
+        let mut press = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(TraceError::Io)?;
+            if line.trim().is_empty() {
+                if !press.is_empty() {
+                    presses.push(std::mem::take(&mut press));
+                }
+                continue;
+            }
+            let pulse = TracedPulse::parse(&line)
+                .ok_or_else(|| TraceError::NotAPulse { line: i + 1, text: line.clone() })?;
+            press.push(pulse);
+        }
+        if !press.is_empty() {
+            presses.push(press);
+        }
+        Ok(Self { presses })
+    }
+
+    /// Compares traces press by press and pulse by pulse, treating `self` as the expected one.
+    pub fn diff(&self, actual: &Trace) -> Vec<TraceDifference> {
+        let mut differences = vec![];
+        let press_count = self.presses.len().max(actual.presses.len());
+        for press in 0..press_count {
+            let expected_pulses = self.presses.get(press).map_or(&[][..], Vec::as_slice);
+            let actual_pulses = actual.presses.get(press).map_or(&[][..], Vec::as_slice);
+            for pulse in 0..expected_pulses.len().max(actual_pulses.len()) {
+                let expected = expected_pulses.get(pulse);
+                let actual = actual_pulses.get(pulse);
+                if expected != actual {
+                    differences.push(TraceDifference {
+                        press: press + 1,
+                        pulse: pulse + 1,
+                        expected: expected.cloned(),
+                        actual: actual.cloned(),
+                    });
+                }
+            }
+        }
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules_network::ModulesNetwork;
+    use std::io::{BufReader, ErrorKind, Read};
+    use crate::trace::{Trace, TraceDifference, TraceError, TracedPulse};
+
+    const EXAMPLE_1: &str = "\
+broadcaster -> a, b, c
+%a -> b
+%b -> c
+%c -> inv
+&inv -> a";
+
+    const EXAMPLE_1_TRANSCRIPT: &str = "\
+button -low-> broadcaster
+broadcaster -low-> a
+broadcaster -low-> b
+broadcaster -low-> c
+a -high-> b
+b -high-> c
+c -high-> inv
+inv -low-> a
+a -low-> b
+b -low-> c
+c -low-> inv
+inv -high-> a
+";
+
+    const EXAMPLE_2: &str = "\
+broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output";
+
+    const EXAMPLE_2_TRANSCRIPT: &str = "\
+button -low-> broadcaster
+broadcaster -low-> a
+a -high-> inv
+a -high-> con
+inv -low-> b
+con -high-> output
+b -high-> con
+con -low-> output
+
+button -low-> broadcaster
+broadcaster -low-> a
+a -low-> inv
+a -low-> con
+inv -high-> b
+con -high-> output
+
+button -low-> broadcaster
+broadcaster -low-> a
+a -high-> inv
+a -high-> con
+inv -low-> b
+con -low-> output
+b -low-> con
+con -high-> output
+
+button -low-> broadcaster
+broadcaster -low-> a
+a -low-> inv
+a -low-> con
+inv -high-> b
+con -high-> output
+";
+
+    fn record(input: &str, presses: usize) -> Trace {
+        Trace::record(&mut ModulesNetwork::from_reader(input.as_bytes()), presses)
+    }
+
+    #[test]
+    fn matches_example_transcripts() {
+        for (input, transcript, presses) in [(EXAMPLE_1, EXAMPLE_1_TRANSCRIPT, 1), (EXAMPLE_2, EXAMPLE_2_TRANSCRIPT, 4)] {
+            let expected = Trace::from_reader(transcript.as_bytes()).unwrap();
+
+            let actual = record(input, presses);
+
+            assert_eq!(expected.presses().len(), presses);
+            assert_eq!(expected.diff(&actual), []);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let trace = record(EXAMPLE_2, 4);
+        let mut written = vec![];
+        trace.write_to(&mut written).unwrap();
+
+        assert_eq!(String::from_utf8(written.clone()).unwrap(), EXAMPLE_2_TRANSCRIPT);
+        assert_eq!(Trace::from_reader(written.as_slice()).unwrap(), trace);
+    }
+
+    #[test]
+    fn diffs_edited_networks() {
+        let edited = EXAMPLE_2.replace("%a -> inv, con", "%a -> con, inv");
+
+        let differences = record(EXAMPLE_2, 1).diff(&record(&edited, 1));
+
+        let pulse = |s| TracedPulse::parse(s).unwrap();
+        assert_eq!(differences[0], TraceDifference {
+            press: 1,
+            pulse: 3,
+            expected: Some(pulse("a -high-> inv")),
+            actual: Some(pulse("a -high-> con")),
+        });
+        assert_eq!(differences[0].to_string(), "press 1, pulse 3: expected a -high-> inv, got a -high-> con");
+    }
+
+    #[test]
+    fn reports_missing_presses() {
+        let differences = record(EXAMPLE_1, 2).diff(&record(EXAMPLE_1, 1));
+
+        assert_eq!(differences.len(), 12);
+        assert!(differences.iter().all(|d| d.press == 2 && d.actual.is_none()));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let result = Trace::from_reader("button -low-> broadcaster\nbroadcaster -> a\n".as_bytes());
+
+        assert!(matches!(result, Err(TraceError::NotAPulse { line: 2, .. })));
+    }
+
+    #[test]
+    fn reports_read_errors() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let result = Trace::from_reader(BufReader::new(Failing));
+
+        assert!(matches!(result, Err(TraceError::Io(err)) if err.kind() == ErrorKind::BrokenPipe));
+    }
+}
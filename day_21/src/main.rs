@@ -1,12 +1,12 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use aoc_shared::coords2d::Coords2D;
 use aoc_shared::direction::DIRECTIONS;
-use aoc_shared::map2d::CharMap;
+use aoc_shared::map2d::{CharMap, Map2D};
 
 fn main() {
-    // part_1();
+    part_1();
     part_2();
 }
 
@@ -18,14 +18,11 @@ fn part_1() {
     let starting_position = map.find_starting_position().unwrap();
     println!("Starting at {:?}", starting_position);
 
-    let mut positions = HashSet::default();
-    positions.insert(starting_position);
-
-    for i in 1..=64 {
-        positions = map.get_next_positions(&positions);
-        println!("{} step/s = {} positions", i, positions.len());
-        // println!("{:?}", positions);
+    let distances = map.distances_from(starting_position);
+    if std::env::args().any(|arg| arg == "--show") {
+        map.display_tiles(&distances, 64);
     }
+    println!("64 step/s = {} positions", distances.reachable_in_exactly(64));
 }
 
 fn part_2() {
@@ -35,6 +32,13 @@ fn part_2() {
 
     let count = map.get_infinite_map_tiles_count(26501365);
     println!("Result: {}", count);
+
+    let fitted = map.get_infinite_map_tiles_count_by_fit(26501365);
+    if fitted == count {
+        println!("Quadratic fit agrees");
+    } else {
+        println!("Quadratic fit disagrees: {}", fitted);
+    }
 }
 
 fn read_file() -> impl BufRead {
@@ -52,16 +56,12 @@ struct OddEven {
 
 impl OddEven {
     pub fn get_mut(&mut self, i: usize) -> &mut usize {
-        if i % 2 == 0 {
+        if i.is_multiple_of(2) {
             &mut self.even
         } else {
             &mut self.odd
         }
     }
-    
-    pub fn total(&self) -> usize {
-        self.odd + self.even
-    }
 }
 
 struct TileMap(CharMap);
@@ -76,30 +76,41 @@ impl TileMap {
         for y in 0..self.0.height() {
             for x in 0..self.0.width() {
                 let point = Coords2D(x, y);
-                match self.0.get(point) {
-                    Some('S') => return Some(point),
-                    _ => {},
+                if let Some('S') = self.0.get(point) {
+                    return Some(point);
                 }
             }
         }
         None
     }
 
-    pub fn get_next_positions(&self, current_positions: &HashSet<Coords2D>) -> HashSet<Coords2D> {
-        let mut next_positions = HashSet::with_capacity(current_positions.len());
-        for &pos in current_positions {
+    /// Walks the map once from `source`, recording the fewest steps needed to reach every tile.
+    pub fn distances_from(&self, source: Coords2D) -> DistanceMap {
+        let mut distances = Map2D::filled_with(&None, self.0.width(), self.0.height());
+        if self.0.get(source).is_none_or(|&c| c == '#') {
+            return DistanceMap(distances);
+        }
+        *distances.get_mut(source).unwrap() = Some(0);
+
+        let mut queue = VecDeque::from([(source, 0)]);
+        while let Some((pos, distance)) = queue.pop_front() {
             for direction in DIRECTIONS {
-                let next_possible = pos
-                    .try_move_one(direction)
-                    .and_then(|p| self.0.get(p).is_some_and(|&c| c != '#').then_some(p));
-                if let Some(pos) = next_possible {
-                    next_positions.insert(pos);
+                let Some(next) = pos.try_move_one(direction) else {
+                    continue;
+                };
+                if self.0.get(next).is_none_or(|&c| c == '#') {
+                    continue;
+                }
+                let next_distance = distances.get_mut(next).unwrap();
+                if next_distance.is_none() {
+                    *next_distance = Some(distance + 1);
+                    queue.push_back((next, distance + 1));
                 }
             }
         }
-        next_positions
+        DistanceMap(distances)
     }
-    
+
     pub fn get_infinite_map_tiles_count(&self, steps: usize) -> usize {
         println!("Calculating {} step/s", steps);
         
@@ -112,19 +123,26 @@ impl TileMap {
         let starting_position = self.find_starting_position().expect("No starting point");
         println!("Starting at {:?}", starting_position);
 
-        let x_center = self.0.width() - ((self.0.width() + 1) / 2);
-        let y_center = self.0.height() - ((self.0.height() + 1) / 2);
+        let x_center = self.0.width() - self.0.width().div_ceil(2);
+        let y_center = self.0.height() - self.0.height().div_ceil(2);
         if starting_position.0 != x_center || starting_position.1 != y_center {
             panic!("Not starting at center {:?}", (x_center, y_center));
         }
 
+        // The odd and even map counts below rely on the centre being an odd number of steps away
+        // from the border, as it is in the puzzle input. The fit doesn't care, it just walks more.
+        if x_center.is_multiple_of(2) {
+            println!("Half map length is even, falling back to the quadratic fit");
+            return self.get_infinite_map_tiles_count_by_fit(steps);
+        }
+
         let excess_steps = steps - x_center;
-        if excess_steps % map_length != 0 {
+        if !excess_steps.is_multiple_of(map_length) {
             panic!("Not a perfect loop!")
         }
         let loops = excess_steps / map_length;
         println!("Loops: {}", loops);
-        if loops % 2 != 0 {
+        if !loops.is_multiple_of(2) {
             panic!("Loops have to be even");
         }
         
@@ -134,10 +152,6 @@ impl TileMap {
         println!("Odd map count: {}", odd_maps);
         println!("Even map count: {}", even_maps);
         
-        // 616583511703756
-        // 616583483179597
-        // 616583481763430
-        
         let tips_count = self.get_possible_tips_tiles(starting_position);
         let small_corners_count = self.get_possible_small_corners_tiles(starting_position);
         let big_corners_count = self.get_possible_big_corners_tiles(starting_position);
@@ -146,34 +160,69 @@ impl TileMap {
             (big_corners_count * (loops - 1))
     }
     
-    fn get_possible_tiles_from(&self, point: Coords2D, steps: usize) -> HashSet<Coords2D> {
-        let mut positions = HashSet::default();
-        positions.insert(point);
-        for _ in 1..=steps {
-            positions = self.get_next_positions(&positions);
+    /// Counts the tiles reachable in exactly `steps` on the infinite garden by walking it for
+    /// three step counts one map length apart and extrapolating the quadratic through them.
+    pub fn get_infinite_map_tiles_count_by_fit(&self, steps: usize) -> usize {
+        if self.0.width() != self.0.height() {
+            panic!("Non-squared maps not supported");
         }
-        positions
+        let map_length = self.0.width();
+        let remainder = steps % map_length;
+        let samples = [remainder, remainder + map_length, remainder + 2 * map_length];
+        let x = (steps / map_length) as i128;
+        if x < 2 {
+            return self.get_infinite_map_tiles_count_brute_force(&[steps])[0];
+        }
+
+        let counts = self.get_infinite_map_tiles_count_brute_force(&samples);
+        let [y0, y1, y2] = [counts[0] as i128, counts[1] as i128, counts[2] as i128];
+        let first_difference = y1 - y0;
+        let second_difference = y2 - 2 * y1 + y0;
+        (y0 + x * first_difference + x * (x - 1) / 2 * second_difference) as usize
     }
-    
+
+    /// Walks a tiled copy of the map big enough to hold every walk, counting the tiles reachable
+    /// in exactly each of the given steps.
+    pub fn get_infinite_map_tiles_count_brute_force(&self, steps: &[usize]) -> Vec<usize> {
+        let starting_position = self.find_starting_position().expect("No starting point");
+        let max_steps = steps.iter().copied().max().unwrap_or(0);
+        let radius = max_steps / self.0.width().min(self.0.height()) + 1;
+        let tiled = self.tiled(radius);
+        let source = Coords2D(
+            starting_position.0 + radius * self.0.width(),
+            starting_position.1 + radius * self.0.height(),
+        );
+
+        let distances = tiled.distances_from(source);
+        steps.iter().map(|&s| distances.reachable_in_exactly(s)).collect()
+    }
+
+    /// The map repeated `radius` times on every side of itself.
+    fn tiled(&self, radius: usize) -> TileMap {
+        let copies = 2 * radius + 1;
+        let (width, height) = (self.0.width(), self.0.height());
+        let mut tiled = CharMap::filled_with(&'.', width * copies, height * copies);
+        for y in 0..height * copies {
+            for x in 0..width * copies {
+                let tile = *self.0.get(Coords2D(x % width, y % height)).unwrap();
+                *tiled.get_mut(Coords2D(x, y)).unwrap() = tile;
+            }
+        }
+        TileMap(tiled)
+    }
+
+    fn get_possible_tiles_from(&self, point: Coords2D, steps: usize) -> usize {
+        self.distances_from(point).reachable_in_exactly(steps)
+    }
+
     fn get_possible_odd_and_even_tiles(&self, starting_point: Coords2D) -> OddEven {
-        let mut positions = HashSet::default();
-        positions.insert(starting_point);
         let mut counts = OddEven::default();
-        let mut i = 0;
-        loop {
-            positions = self.get_next_positions(&positions);
-            i += 1;
-            let count = positions.len();
-
-            let selected_count = counts.get_mut(i);
-            if *selected_count == count {
-                break;
-            }
-            *selected_count = count;
+        for distance in self.distances_from(starting_point).0.iter().flatten() {
+            *counts.get_mut(*distance as usize) += 1;
         }
         counts
     }
-    
+
     fn get_possible_tips_tiles(&self, starting_point: Coords2D) -> usize {
         let length = self.0.width() - 1;
         let top = self.get_possible_tiles_from(Coords2D(starting_point.0, length), length);
@@ -181,12 +230,7 @@ impl TileMap {
         let bottom = self.get_possible_tiles_from(Coords2D(starting_point.0, 0), length);
         let left = self.get_possible_tiles_from(Coords2D(length, starting_point.1), length);
         
-        // self.display_tiles(&top);
-        // self.display_tiles(&right);
-        // self.display_tiles(&bottom);
-        // self.display_tiles(&left);
-
-        top.len() + right.len() + bottom.len() + left.len()
+        top + right + bottom + left
     }
 
     fn get_possible_small_corners_tiles(&self, starting_point: Coords2D) -> usize {
@@ -197,12 +241,7 @@ impl TileMap {
         let bottom_left = self.get_possible_tiles_from(Coords2D(0, map_max), length);
         let top_left = self.get_possible_tiles_from(Coords2D(0, 0), length);
         
-        // self.display_tiles(&top_right);
-        // self.display_tiles(&bottom_right);
-        // self.display_tiles(&bottom_left);
-        // self.display_tiles(&top_left);
-        
-        top_right.len() + bottom_right.len() + bottom_left.len() + top_left.len()
+        top_right + bottom_right + bottom_left + top_left
     }
 
     fn get_possible_big_corners_tiles(&self, starting_point: Coords2D) -> usize {
@@ -213,19 +252,14 @@ impl TileMap {
         let bottom_left = self.get_possible_tiles_from(Coords2D(0, map_max), length);
         let top_left = self.get_possible_tiles_from(Coords2D(0, 0), length);
         
-        // self.display_tiles(&top_right);
-        // self.display_tiles(&bottom_right);
-        // self.display_tiles(&bottom_left);
-        // self.display_tiles(&top_left);
-        
-        top_right.len() + bottom_right.len() + bottom_left.len() + top_left.len()
+        top_right + bottom_right + bottom_left + top_left
     }
     
-    pub fn display_tiles(&self, points: &HashSet<Coords2D>) {
+    pub fn display_tiles(&self, distances: &DistanceMap, steps: usize) {
         for y in 0..self.0.height() {
             for x in 0..self.0.width() {
                 let point = Coords2D(x, y);
-                if points.contains(&point) {
+                if distances.is_reachable_in_exactly(point, steps) {
                     print!("\u{1b}[31;1m");
                     print!("O");
                     print!("\u{1b}[0m");
@@ -239,3 +273,140 @@ impl TileMap {
         }
     }
 }
+
+/// Fewest steps from a source to every tile, `None` for rocks and tiles cut off from it.
+pub struct DistanceMap(Map2D<Option<u32>>);
+
+impl DistanceMap {
+    pub fn get(&self, point: Coords2D) -> Option<u32> {
+        self.0.get(point).copied().flatten()
+    }
+
+    /// Whether a walk of exactly `steps` can end on `point`. Walks can waste steps two at a time
+    /// by going back and forth, so it's enough for the shortest one to fit and share its parity.
+    pub fn is_reachable_in_exactly(&self, point: Coords2D, steps: usize) -> bool {
+        self.get(point).is_some_and(|d| Self::fits(d, steps))
+    }
+
+    pub fn reachable_in_exactly(&self, steps: usize) -> usize {
+        self.0.iter().flatten().filter(|&&d| Self::fits(d, steps)).count()
+    }
+
+    fn fits(distance: u32, steps: usize) -> bool {
+        let distance = distance as usize;
+        distance <= steps && distance % 2 == steps % 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use aoc_shared::coords2d::Coords2D;
+    use aoc_shared::direction::DIRECTIONS;
+    use aoc_shared::rng::Lcg;
+    use crate::TileMap;
+
+    const EXAMPLE: &str = "\
+...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........
+";
+
+    fn naive_positions(map: &TileMap, source: Coords2D, steps: usize) -> usize {
+        let mut positions = HashSet::from([source]);
+        for _ in 0..steps {
+            positions = positions.iter()
+                .flat_map(|pos| DIRECTIONS.iter().filter_map(|&d| pos.try_move_one(d)))
+                .filter(|&p| map.0.get(p).is_some_and(|&c| c != '#'))
+                .collect();
+        }
+        positions.len()
+    }
+
+    /// A map shaped like the puzzle inputs: starting in the middle of an odd sized map, with the
+    /// middle row and column and the border clear of rocks. Inputs also have an odd half size.
+    fn generate_map(size: usize, seed: u64) -> TileMap {
+        let mut rng = Lcg::new(seed);
+        let center = size / 2;
+        let mut input = String::new();
+        for y in 0..size {
+            for x in 0..size {
+                let roll = rng.below(100);
+                let clear = x == center || y == center || x == 0 || y == 0 || x == size - 1 || y == size - 1;
+                input.push(match () {
+                    _ if (x, y) == (center, center) => 'S',
+                    _ if !clear && roll < 15 => '#',
+                    _ => '.',
+                });
+            }
+            input.push('\n');
+        }
+        TileMap::from_reader(&mut input.as_bytes())
+    }
+
+    #[test]
+    fn closed_form_matches_walking_the_garden() {
+        for seed in 0..5 {
+            let map = generate_map(15, seed);
+            for loops in [2, 4, 6] {
+                let steps = 7 + loops * 15;
+                let walked = map.get_infinite_map_tiles_count_brute_force(&[steps])[0];
+
+                assert_eq!(map.get_infinite_map_tiles_count_by_fit(steps), walked, "seed {seed}, {steps} steps");
+                assert_eq!(map.get_infinite_map_tiles_count(steps), walked, "seed {seed}, {steps} steps");
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_fit_for_even_half_lengths() {
+        for seed in 0..3 {
+            let map = generate_map(13, seed);
+            for loops in [2, 4] {
+                let steps = 6 + loops * 13;
+                let walked = map.get_infinite_map_tiles_count_brute_force(&[steps])[0];
+
+                assert_eq!(map.get_infinite_map_tiles_count(steps), walked, "seed {seed}, {steps} steps");
+            }
+        }
+    }
+
+    #[test]
+    fn closed_form_matches_quadratic_fit() {
+        for seed in 0..5 {
+            let map = generate_map(23, seed);
+            for loops in [10, 1000, 202300] {
+                let steps = 11 + loops * 23;
+
+                assert_eq!(map.get_infinite_map_tiles_count(steps), map.get_infinite_map_tiles_count_by_fit(steps), "seed {seed}, {steps} steps");
+            }
+        }
+    }
+
+    #[test]
+    fn counts_example_steps() {
+        let map = TileMap::from_reader(&mut EXAMPLE.as_bytes());
+        let start = map.find_starting_position().unwrap();
+
+        assert_eq!(map.distances_from(start).reachable_in_exactly(6), 16);
+    }
+
+    #[test]
+    fn matches_step_by_step_walk() {
+        let map = TileMap::from_reader(&mut EXAMPLE.as_bytes());
+        for source in [map.find_starting_position().unwrap(), Coords2D(0, 0), Coords2D(10, 4)] {
+            let distances = map.distances_from(source);
+            for steps in 0..30 {
+                assert_eq!(distances.reachable_in_exactly(steps), naive_positions(&map, source, steps), "{source:?} {steps}");
+            }
+        }
+    }
+}